@@ -0,0 +1,167 @@
+use std::fmt::Debug;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Sub};
+
+/// Set of small integers (`0..64 * N`) packed into `N` machine words.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub(crate) struct BitSet<const N: usize>([u64; N]);
+
+pub(crate) type BitSet64 = BitSet<1>;
+#[allow(dead_code)] // no puzzle has more than 64 items yet
+pub(crate) type BitSet128 = BitSet<2>;
+
+impl<const N: usize> BitSet<N> {
+    pub const CAPACITY: usize = 64 * N;
+
+    pub fn new() -> Self {
+        Self([0; N])
+    }
+
+    /// Set containing every element below `n`.
+    pub fn full(n: usize) -> Self {
+        (0..n).collect()
+    }
+
+    fn locate(i: usize) -> (usize, u64) {
+        assert!(i < Self::CAPACITY, "{i} does not fit in a {N}-word bitset");
+        (i / 64, 1 << (i % 64))
+    }
+
+    /// Adds an element, returns whether it was not there before.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (word, bit) = Self::locate(i);
+        let absent = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        absent
+    }
+
+    /// Removes an element, returns whether it was there.
+    #[allow(dead_code)]
+    pub fn remove(&mut self, i: usize) -> bool {
+        let (word, bit) = Self::locate(i);
+        let present = self.0[word] & bit != 0;
+        self.0[word] &= !bit;
+        present
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        i < Self::CAPACITY && self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_subset(&self, other: &Self) -> bool {
+        (*self & !*other).is_empty()
+    }
+
+    /// Elements in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, &word)| {
+            let mut w = word;
+            std::iter::from_fn(move || {
+                (w != 0).then(|| {
+                    let bit = w.trailing_zeros() as usize;
+                    w &= w - 1;
+                    i * 64 + bit
+                })
+            })
+        })
+    }
+}
+
+impl<const N: usize> Default for BitSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Debug for BitSet<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize> FromIterator<usize> for BitSet<N> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut result = Self::new();
+        for i in iter {
+            result.insert(i);
+        }
+        result
+    }
+}
+
+macro_rules! impl_bit_op {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl<const N: usize> $trait for BitSet<N> {
+            type Output = Self;
+
+            fn $method(self, rhs: Self) -> Self {
+                let mut result = self;
+                for (a, b) in result.0.iter_mut().zip(rhs.0) {
+                    *a = $op(*a, b);
+                }
+                result
+            }
+        }
+    };
+}
+
+impl_bit_op!(BitOr, bitor, |a, b| a | b);
+impl_bit_op!(BitAnd, bitand, |a, b| a & b);
+impl_bit_op!(BitXor, bitxor, |a, b| a ^ b);
+impl_bit_op!(Sub, sub, |a: u64, b: u64| a & !b);
+
+impl<const N: usize> Not for BitSet<N> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0.map(|w| !w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitSet, BitSet128, BitSet64};
+
+    #[test]
+    fn test_insert_remove() {
+        let mut s = BitSet64::new();
+        assert!(s.insert(3));
+        assert!(!s.insert(3));
+        assert!(s.insert(63));
+        assert!(s.contains(3) && s.contains(63) && !s.contains(4));
+        assert_eq!(2, s.len());
+        assert!(s.remove(3));
+        assert!(!s.remove(3));
+        assert_eq!(vec![63], s.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a: BitSet128 = [1, 2, 70, 100].into_iter().collect();
+        let b: BitSet128 = [2, 3, 100].into_iter().collect();
+        assert_eq!(vec![1, 2, 3, 70, 100], (a | b).iter().collect::<Vec<_>>());
+        assert_eq!(vec![2, 100], (a & b).iter().collect::<Vec<_>>());
+        assert_eq!(vec![1, 70], (a - b).iter().collect::<Vec<_>>());
+        assert_eq!(vec![1, 3, 70], (a ^ b).iter().collect::<Vec<_>>());
+        assert!((a & b).is_subset(&a));
+        assert!(!a.is_subset(&b));
+        assert_eq!(128 - 4, (!a).len());
+    }
+
+    #[test]
+    fn test_wide() {
+        let s = BitSet::<4>::full(200);
+        assert_eq!(200, s.len());
+        assert_eq!(Some(199), s.iter().last());
+        assert!(!s.contains(200));
+        assert_eq!("{0, 5}", format!("{:?}", BitSet64::from_iter([5, 0])));
+    }
+}
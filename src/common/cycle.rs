@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A repeating stretch of a simulation: the state after `start` steps has the
+/// same key as the state after `start + length` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+/// Finds a cycle by remembering the key of every state seen so far. Each state is
+/// stepped only once, which suits states that are cheap to key but cannot be cloned.
+#[allow(dead_code)] // day 17 has big keys and uses `find_cycle_brent`
+pub(crate) fn find_cycle<S, K: Hash + Eq>(
+    mut state: S,
+    key: impl Fn(&S) -> K,
+    mut step: impl FnMut(&mut S),
+) -> Cycle {
+    let mut seen = HashMap::new();
+    for i in 0.. {
+        if let Some(start) = seen.insert(key(&state), i) {
+            return Cycle {
+                start,
+                length: i - start,
+            };
+        }
+        step(&mut state);
+    }
+    unreachable!()
+}
+
+/// Brent's algorithm: same result as [`find_cycle`], but only two states are kept
+/// around at a time, so keys can be as big as a whole chamber surface.
+pub(crate) fn find_cycle_brent<S: Clone, K: Eq>(
+    state: S,
+    key: impl Fn(&S) -> K,
+    mut step: impl FnMut(&mut S),
+) -> Cycle {
+    // find the cycle length by teleporting the tortoise at powers of two
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = state.clone();
+    let mut hare = state.clone();
+    step(&mut hare);
+    while key(&tortoise) != key(&hare) {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        step(&mut hare);
+        length += 1;
+    }
+
+    // then walk two states `length` apart until they meet
+    let mut tortoise = state.clone();
+    let mut hare = state;
+    for _ in 0..length {
+        step(&mut hare);
+    }
+    let mut start = 0;
+    while key(&tortoise) != key(&hare) {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Value of a metric that grows by the same amount on every cycle repetition
+/// (e.g. tower height) after `n` steps, simulating no further than one cycle.
+pub(crate) fn extrapolate<S>(
+    mut state: S,
+    mut step: impl FnMut(&mut S),
+    metric: impl Fn(&S) -> usize,
+    cycle: Cycle,
+    n: usize,
+) -> usize {
+    let mut values = vec![metric(&state)];
+    for _ in 0..n.min(cycle.start + cycle.length) {
+        step(&mut state);
+        values.push(metric(&state));
+    }
+    if n < values.len() {
+        return values[n];
+    }
+    let growth = values[cycle.start + cycle.length] - values[cycle.start];
+    let repeats = (n - cycle.start) / cycle.length;
+    let offset = (n - cycle.start) % cycle.length;
+    values[cycle.start + offset] + repeats * growth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extrapolate, find_cycle, find_cycle_brent, Cycle};
+
+    // 3 -> 10 -> 5 -> 16 -> 8 -> 4 -> 2 -> 1 -> 4 -> ...
+    fn collatz(x: &mut u64) {
+        *x = if x.is_multiple_of(2) {
+            *x / 2
+        } else {
+            3 * *x + 1
+        };
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let expected = Cycle {
+            start: 5,
+            length: 3,
+        };
+        assert_eq!(expected, find_cycle(3, |&x| x, collatz));
+        assert_eq!(expected, find_cycle_brent(3, |&x| x, collatz));
+        let from_start = Cycle {
+            start: 0,
+            length: 3,
+        };
+        assert_eq!(from_start, find_cycle(4, |&x| x, collatz));
+        assert_eq!(from_start, find_cycle_brent(4, |&x| x, collatz));
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        // position on a 4-cell ring plus the number of full laps
+        let step = |s: &mut (usize, usize)| {
+            s.0 += 1;
+            if s.0 == 4 {
+                s.0 = 0;
+                s.1 += 1;
+            }
+        };
+        let cycle = find_cycle((0, 0), |s| s.0, step);
+        assert_eq!(
+            Cycle {
+                start: 0,
+                length: 4
+            },
+            cycle
+        );
+        let metric = |s: &(usize, usize)| s.1 * 4 + s.0;
+        assert_eq!(2, extrapolate((0, 0), step, metric, cycle, 2));
+        assert_eq!(
+            1_000_003,
+            extrapolate((0, 0), step, metric, cycle, 1_000_003)
+        );
+    }
+}
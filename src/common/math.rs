@@ -0,0 +1,216 @@
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+
+pub(crate) fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Greatest common divisor of all numbers, 0 for none.
+#[allow(dead_code)]
+pub(crate) fn gcd_all(nums: impl IntoIterator<Item = u64>) -> u64 {
+    nums.into_iter().fold(0, gcd)
+}
+
+/// Least common multiple of all numbers, 1 for none, `None` if it does not fit in a `u64`.
+pub(crate) fn lcm_all(nums: impl IntoIterator<Item = u64>) -> Option<u64> {
+    nums.into_iter().try_fold(1, |acc: u64, x| {
+        if x == 0 {
+            Some(0)
+        } else {
+            (acc / gcd(acc, x)).checked_mul(x)
+        }
+    })
+}
+
+/// Extended Euclid: returns `(g, x, y)` such that `a * x + b * y = g = gcd(a, b)`.
+#[allow(dead_code)]
+pub(crate) fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+/// Chinese remainder theorem for `(remainder, modulus)` pairs, moduli need not be coprime.
+/// Returns `(x, m)` where `x` is the smallest non-negative solution and `m` the lcm of the
+/// moduli, or `None` if a modulus is not positive, the congruences contradict each
+/// other or `m` overflows.
+#[allow(dead_code)]
+pub(crate) fn crt(congruences: impl IntoIterator<Item = (i64, i64)>) -> Option<(i64, i64)> {
+    congruences
+        .into_iter()
+        .try_fold((0_i64, 1_i64), |(r1, m1), (r2, m2)| {
+            if m2 <= 0 {
+                return None;
+            }
+            // done in 128 bits so that intermediate products cannot overflow
+            let (r1, m1) = (i128::from(r1), i128::from(m1));
+            let (r2, m2) = (i128::from(r2), i128::from(m2));
+            let (g, coef, _) = ext_gcd(m1, m2);
+            if (r2 - r1) % g != 0 {
+                return None;
+            }
+            let lcm = m1 / g * m2;
+            let steps = (r2 - r1) / g * coef % (m2 / g);
+            let x = (r1 + m1 * steps).rem_euclid(lcm);
+            Some((i64::try_from(x).ok()?, i64::try_from(lcm).ok()?))
+        })
+}
+
+/// Integer modulo a runtime modulus; products go through 128 bits so they never overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ModInt {
+    value: u64,
+    modulus: u64,
+}
+
+impl ModInt {
+    pub fn new(value: u64, modulus: u64) -> Self {
+        assert!(modulus > 0, "modulus must be positive");
+        Self {
+            value: value % modulus,
+            modulus,
+        }
+    }
+
+    pub fn value(self) -> u64 {
+        self.value
+    }
+
+    #[allow(dead_code)]
+    pub fn modulus(self) -> u64 {
+        self.modulus
+    }
+
+    fn with_value(self, value: u128) -> Self {
+        // once reduced below the modulus it fits in a u64 again
+        Self::new(
+            u64::try_from(value % u128::from(self.modulus)).unwrap(),
+            self.modulus,
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn pow(self, mut exp: u64) -> Self {
+        let mut base = self;
+        let mut result = Self::new(1, self.modulus);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exp >>= 1;
+        }
+        result
+    }
+
+    /// Multiplicative inverse, if the value is coprime with the modulus.
+    #[allow(dead_code)]
+    pub fn inv(self) -> Option<Self> {
+        let m = i128::from(self.modulus);
+        let (g, x, _) = ext_gcd(i128::from(self.value), m);
+        (g == 1).then(|| Self::new(u64::try_from(x.rem_euclid(m)).unwrap(), self.modulus))
+    }
+}
+
+impl Display for ModInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (mod {})", self.value, self.modulus)
+    }
+}
+
+impl Add for ModInt {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        debug_assert_eq!(self.modulus, rhs.modulus);
+        self.with_value(u128::from(self.value) + u128::from(rhs.value))
+    }
+}
+
+impl Sub for ModInt {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl Neg for ModInt {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.with_value(u128::from(self.modulus - self.value))
+    }
+}
+
+impl Mul for ModInt {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        debug_assert_eq!(self.modulus, rhs.modulus);
+        self.with_value(u128::from(self.value) * u128::from(rhs.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crt, ext_gcd, gcd_all, lcm_all, ModInt};
+
+    #[test]
+    fn test_gcd_lcm() {
+        assert_eq!(6, gcd_all([12, 18, 30]));
+        assert_eq!(0, gcd_all([]));
+        assert_eq!(Some(96577), lcm_all([23, 19, 13, 17]));
+        assert_eq!(Some(12), lcm_all([4, 6]));
+        assert_eq!(Some(1), lcm_all([]));
+        assert_eq!(None, lcm_all([u64::MAX, u64::MAX - 1]));
+    }
+
+    #[test]
+    fn test_ext_gcd() {
+        let (g, x, y) = ext_gcd(240, 46);
+        assert_eq!(2, g);
+        assert_eq!(2, 240 * x + 46 * y);
+        assert_eq!((3, 0, -1), ext_gcd(0, -3));
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(Some((23, 105)), crt([(2, 3), (3, 5), (2, 7)]));
+        assert_eq!(Some((10, 12)), crt([(4, 6), (2, 4)]));
+        assert_eq!(None, crt([(1, 6), (2, 4)]));
+        assert_eq!(Some((0, 1)), crt([]));
+        assert_eq!(None, crt([(2, 3), (0, 0)]));
+        assert_eq!(None, crt([(1, -4)]));
+    }
+
+    #[test]
+    fn test_mod_int() {
+        let m = u64::MAX - 58; // a large prime
+        let a = ModInt::new(m - 1, m);
+        assert_eq!(1, (a * a).value());
+        assert_eq!(m - 2, (a + a).value());
+        assert_eq!(2, (ModInt::new(1, m) - a).value());
+        assert_eq!(
+            1,
+            (ModInt::new(12345, m) * ModInt::new(12345, m).inv().unwrap()).value()
+        );
+        assert_eq!(24, ModInt::new(2, 1000).pow(10).value());
+        assert_eq!(None, ModInt::new(4, 6).inv());
+        assert_eq!("4 (mod 7)", ModInt::new(11, 7).to_string());
+    }
+}
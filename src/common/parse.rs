@@ -0,0 +1,145 @@
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use regex::{Captures, Regex};
+
+/// A block of input lines separated from the others by blank lines.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Paragraph<'a> {
+    /// Position among the paragraphs, counting from 1.
+    pub number: usize,
+    /// Index of the first line in the whole input.
+    pub offset: usize,
+    pub lines: &'a [String],
+}
+
+impl<'a> Paragraph<'a> {
+    /// Parses every line, naming the offending input line on error.
+    pub fn parse_lines<T>(&self, f: impl Fn(&'a str) -> Result<T>) -> Result<Vec<T>> {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                f(line).wrap_err_with(|| format!("line {}: '{line}'", self.offset + i + 1))
+            })
+            .collect()
+    }
+
+    /// Matches the `i`-th line of the paragraph against a regex.
+    pub fn captures(&self, i: usize, re: &Regex) -> Result<Captures<'a>> {
+        let line = self
+            .lines
+            .get(i)
+            .ok_or_else(|| eyre!("line {}: missing", self.offset + i + 1))?;
+        re.captures(line)
+            .ok_or_else(|| eyre!("line {}: '{line}' does not match {re}", self.offset + i + 1))
+    }
+
+    /// One number per line.
+    pub fn nums<T: FromStr<Err = ParseIntError>>(&self) -> Result<Vec<T>> {
+        self.parse_lines(parse_num)
+    }
+}
+
+/// Splits the input on blank lines.
+pub(crate) fn paragraphs(data: &[String]) -> impl Iterator<Item = Paragraph<'_>> {
+    let mut offset = 0;
+    data.split(String::is_empty)
+        .enumerate()
+        .map(move |(i, lines)| {
+            let p = Paragraph {
+                number: i + 1,
+                offset,
+                lines,
+            };
+            offset += lines.len() + 1;
+            p
+        })
+}
+
+/// Lazily parses paragraphs one by one, naming the offending paragraph on error.
+pub(crate) fn map_paragraphs<'a, T, F: FnMut(&Paragraph<'a>) -> Result<T>>(
+    data: &'a [String],
+    mut f: F,
+) -> impl Iterator<Item = Result<T>> + use<'a, T, F> {
+    paragraphs(data).map(move |p| {
+        f(&p).wrap_err_with(|| format!("paragraph {} (from line {})", p.number, p.offset + 1))
+    })
+}
+
+/// Parses every paragraph, naming the offending paragraph on error.
+pub(crate) fn parse_paragraphs<'a, T>(
+    data: &'a [String],
+    f: impl FnMut(&Paragraph<'a>) -> Result<T>,
+) -> Result<Vec<T>> {
+    map_paragraphs(data, f).collect()
+}
+
+/// Parses an integer of any width, allowing a leading `+`/`-` and `_` between digits.
+pub(crate) fn parse_num<T: FromStr<Err = ParseIntError>>(s: &str) -> Result<T> {
+    let s = s.trim();
+    let chars: Vec<char> = s.chars().collect();
+    let between_digits = |i: usize| {
+        i > 0 && chars[i - 1].is_ascii_digit() && chars.get(i + 1).is_some_and(char::is_ascii_digit)
+    };
+    // any other `_` is left in for the parse to reject
+    let digits: String = (chars.iter().enumerate())
+        .filter(|&(i, &c)| c != '_' || !between_digits(i))
+        .map(|(_, &c)| c)
+        .collect();
+    digits
+        .parse()
+        .wrap_err_with(|| format!("invalid integer '{s}'"))
+}
+
+/// Parses a list of integers such as `79, 98` separated by `sep`.
+pub(crate) fn parse_nums<T: FromStr<Err = ParseIntError>>(s: &str, sep: char) -> Result<Vec<T>> {
+    s.split(sep).map(parse_num).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{paragraphs, parse_num, parse_nums, parse_paragraphs, Paragraph};
+    use crate::common::str2lines;
+
+    #[test]
+    fn test_paragraphs() {
+        let data = str2lines("1\n2\n\n3\n\n\n4");
+        let ps: Vec<_> = paragraphs(&data)
+            .map(|p| (p.number, p.offset, p.lines.len()))
+            .collect();
+        assert_eq!(vec![(1, 0, 2), (2, 3, 1), (3, 5, 0), (4, 6, 1)], ps);
+    }
+
+    #[test]
+    fn test_parse_num() {
+        assert_eq!(-12, parse_num::<i8>("-12").unwrap());
+        assert_eq!(12, parse_num::<u64>("+12").unwrap());
+        assert_eq!(1_000_000, parse_num::<u32>(" 1_000_000 ").unwrap());
+        assert!(parse_num::<u8>("256").is_err());
+        assert!(parse_num::<u8>("-1").is_err());
+        assert_eq!(1_234, parse_num::<u32>("1_2_3_4").unwrap());
+        for s in ["_5", "5_", "1__2", "-_5", "_"] {
+            let err = parse_num::<i32>(s).unwrap_err();
+            assert_eq!(format!("invalid integer '{s}'"), err.to_string());
+        }
+        assert_eq!(vec![79, 98], parse_nums::<u64>("79, 98", ',').unwrap());
+    }
+
+    #[test]
+    fn test_errors_name_paragraph_and_line() {
+        let data = str2lines("1\n2\n\n3\nx");
+        let err = parse_paragraphs(&data, Paragraph::nums::<i32>).unwrap_err();
+        let chain: Vec<_> = err.chain().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "paragraph 2 (from line 4)",
+                "line 5: 'x'",
+                "invalid integer 'x'",
+                "invalid digit found in string"
+            ],
+            chain
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use color_eyre::eyre::Result;
+
+pub(crate) type Rgb = [u8; 3];
+
+/// Dense grid of cells, row 0 at the top, that can be drawn as text or as an image.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Canvas<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+fn to_i64<I: TryInto<i64>>(v: I) -> i64 {
+    let Ok(x) = v.try_into() else {
+        panic!("coordinate does not fit in i64");
+    };
+    x
+}
+
+impl<T> Canvas<T> {
+    /// Cells are given row by row.
+    pub fn new(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert_eq!(width * height, cells.len(), "cells do not fill the canvas");
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Every row must have the same number of cells.
+    pub fn from_rows<R: IntoIterator<Item = T>>(rows: impl IntoIterator<Item = R>) -> Self {
+        let mut cells = vec![];
+        let mut width = None;
+        let mut height = 0;
+        for row in rows {
+            let start = cells.len();
+            cells.extend(row);
+            let len = cells.len() - start;
+            assert_eq!(*width.get_or_insert(len), len, "rows have different widths");
+            height += 1;
+        }
+        Self::new(width.unwrap_or(0), height, cells)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All cells, row by row.
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, x: usize, y: usize) -> &T {
+        &self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) {
+        self.cells[y * self.width + x] = value;
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks() refuses a zero size
+        self.cells.chunks(self.width.max(1)).take(self.height)
+    }
+
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Canvas<U> {
+        Canvas::new(self.width, self.height, self.cells.iter().map(f).collect())
+    }
+
+    /// One line of text per row, without a trailing newline.
+    pub fn ascii(&self, glyph: impl Fn(&T) -> char) -> String {
+        self.rows()
+            .map(|row| row.iter().map(&glyph).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Like [`Canvas::ascii`], with every glyph coloured using 24-bit ANSI escapes.
+    pub fn ansi(&self, glyph: impl Fn(&T) -> char, color: impl Fn(&T) -> Rgb) -> String {
+        let mut lines = vec![];
+        for row in self.rows() {
+            let mut line = String::new();
+            let mut current = None;
+            for cell in row {
+                let c = color(cell);
+                if current != Some(c) {
+                    write!(line, "\x1b[38;2;{};{};{}m", c[0], c[1], c[2]).unwrap();
+                    current = Some(c);
+                }
+                line.push(glyph(cell));
+            }
+            line.push_str("\x1b[0m");
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    /// RGB pixels, each cell drawn as a `scale` x `scale` square.
+    pub fn pixels(&self, scale: usize, color: impl Fn(&T) -> Rgb) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.cells.len() * scale * scale * 3);
+        for row in self.rows() {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|cell| color(cell).repeat(scale))
+                .collect();
+            for _ in 0..scale {
+                result.extend_from_slice(&line);
+            }
+        }
+        result
+    }
+
+    pub fn write_png(&self, w: impl Write, scale: usize, color: impl Fn(&T) -> Rgb) -> Result<()> {
+        let mut encoder = png::Encoder::new(
+            w,
+            u32::try_from(self.width * scale)?,
+            u32::try_from(self.height * scale)?,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels(scale, color))?;
+        Ok(())
+    }
+}
+
+impl<T: Clone> Canvas<T> {
+    /// Every cell blown up into a `scale` x `scale` square.
+    pub fn scaled(&self, scale: usize) -> Self {
+        Self::from_rows(self.rows().flat_map(|row| {
+            let line: Vec<T> = row
+                .iter()
+                .flat_map(|c| std::iter::repeat_n(c.clone(), scale))
+                .collect();
+            std::iter::repeat_n(line, scale)
+        }))
+    }
+
+    /// Canvas covering the bounding box of the given points, `background` elsewhere.
+    /// Screen coordinates: y grows downwards. Returns the canvas and the coordinates
+    /// of its top left corner.
+    pub fn from_sparse<I: TryInto<i64>>(
+        points: impl IntoIterator<Item = ((I, I), T)>,
+        background: T,
+    ) -> (Self, (i64, i64)) {
+        let points: Vec<_> = points
+            .into_iter()
+            .map(|((x, y), v)| ((to_i64(x), to_i64(y)), v))
+            .collect();
+        let Some(min_x) = points.iter().map(|p| p.0 .0).min() else {
+            return (Self::new(0, 0, vec![]), (0, 0));
+        };
+        let min_y = points.iter().map(|p| p.0 .1).min().unwrap();
+        let max_x = points.iter().map(|p| p.0 .0).max().unwrap();
+        let max_y = points.iter().map(|p| p.0 .1).max().unwrap();
+        let width = usize::try_from(max_x - min_x + 1).unwrap();
+        let height = usize::try_from(max_y - min_y + 1).unwrap();
+        let mut canvas = Self::new(width, height, vec![background; width * height]);
+        for ((x, y), v) in points {
+            canvas.set(
+                usize::try_from(x - min_x).unwrap(),
+                usize::try_from(y - min_y).unwrap(),
+                v,
+            );
+        }
+        (canvas, (min_x, min_y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Canvas;
+
+    #[test]
+    fn test_ascii() {
+        let canvas = Canvas::from_rows([[true, false], [false, true]]);
+        assert_eq!("#.\n.#", canvas.ascii(|&x| if x { '#' } else { '.' }));
+        assert_eq!("", Canvas::<bool>::from_rows::<Vec<_>>([]).ascii(|_| '#'));
+    }
+
+    #[test]
+    #[should_panic(expected = "rows have different widths")]
+    fn test_ragged_rows() {
+        Canvas::from_rows([vec![1, 2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn test_scaled() {
+        let canvas = Canvas::from_rows([['a', 'b']]).scaled(2);
+        assert_eq!("aabb\naabb", canvas.ascii(|&c| c));
+    }
+
+    #[test]
+    fn test_sparse() {
+        let (canvas, origin) = Canvas::from_sparse([((-1, 5), 'a'), ((1, 6), 'b')], '.');
+        assert_eq!((-1, 5), origin);
+        assert_eq!("a..\n..b", canvas.ascii(|&c| c));
+        let (empty, _) = Canvas::<char>::from_sparse::<usize>([], '.');
+        assert_eq!(0, empty.width());
+    }
+
+    #[test]
+    fn test_ansi() {
+        let canvas = Canvas::from_rows([[1, 1, 2]]);
+        let red = [255, 0, 0];
+        let blue = [0, 0, 255];
+        assert_eq!(
+            "\x1b[38;2;255;0;0m##\x1b[38;2;0;0;255m#\x1b[0m",
+            canvas.ansi(|_| '#', |&x| if x == 1 { red } else { blue })
+        );
+    }
+
+    #[test]
+    fn test_png() {
+        let canvas = Canvas::from_rows([[0, 255]]);
+        assert_eq!(
+            vec![0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255],
+            canvas.pixels(2, |&x| [x; 3])[..12].to_vec()
+        );
+        let mut png = vec![];
+        canvas.write_png(&mut png, 2, |&x| [x; 3]).unwrap();
+        assert_eq!(b"\x89PNG", &png[..4]);
+    }
+}
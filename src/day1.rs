@@ -1,19 +1,89 @@
-use crate::common::int_groups_data;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::Write;
 
-fn elf_totals(data: &[String]) -> Vec<i32> {
-    let groups = int_groups_data(data);
-    groups.iter().map(|v| v.iter().sum()).collect()
+use color_eyre::eyre::Result;
+
+use crate::common::parse::{map_paragraphs, Paragraph};
+
+struct Elf {
+    /// Position in the input, counting from 1.
+    number: usize,
+    items: usize,
+    total: u64,
+}
+
+impl Elf {
+    fn parse(p: &Paragraph) -> Result<Self> {
+        let items = p.nums::<u64>()?;
+        Ok(Self {
+            number: p.number,
+            items: items.len(),
+            total: items.iter().sum(),
+        })
+    }
 }
 
-pub fn solution1(data: &[String]) -> i32 {
-    elf_totals(data).into_iter().max().unwrap()
+/// Elves in input order, parsed as they are needed.
+fn elves(data: &[String]) -> impl Iterator<Item = Result<Elf>> + '_ {
+    map_paragraphs(data, Elf::parse)
 }
 
-pub fn solution2(data: &[String]) -> i32 {
-    let mut totals = elf_totals(data);
+/// Sum of the `k` largest elf totals, in one pass keeping only `k` totals around.
+pub fn top_total(data: &[String], k: usize) -> Result<u64> {
+    let mut top = BinaryHeap::with_capacity(k + 1);
+    for elf in elves(data) {
+        top.push(Reverse(elf?.total));
+        if top.len() > k {
+            top.pop();
+        }
+    }
+    Ok(top.into_iter().map(|Reverse(total)| total).sum())
+}
+
+/// Every elf's item count and total, followed by summary statistics.
+pub fn report(data: &[String]) -> Result<String> {
+    let mut out = format!("{:>5} {:>6} {:>8}\n", "elf", "items", "total");
+    let mut totals = vec![];
+    for elf in elves(data) {
+        let elf = elf?;
+        writeln!(out, "{:>5} {:>6} {:>8}", elf.number, elf.items, elf.total)?;
+        totals.push(elf.total);
+    }
+    if totals.is_empty() {
+        out.push_str("no elves\n");
+        return Ok(out);
+    }
+
     totals.sort_unstable();
-    totals.reverse();
-    totals.iter().take(3).sum()
+    let n = totals.len();
+    #[allow(clippy::cast_precision_loss)]
+    let mean = totals.iter().sum::<u64>() as f64 / n as f64;
+    #[allow(clippy::cast_precision_loss)]
+    let median = if n % 2 == 0 {
+        (totals[n / 2 - 1] + totals[n / 2]) as f64 / 2.0
+    } else {
+        totals[n / 2] as f64
+    };
+    writeln!(out, "elves: {n}, mean: {mean:.1}, median: {median:.1}")?;
+    if let Some(gap) = totals.windows(2).max_by_key(|w| w[1] - w[0]) {
+        writeln!(
+            out,
+            "max gap: {} (between {} and {})",
+            gap[1] - gap[0],
+            gap[0],
+            gap[1]
+        )?;
+    }
+    Ok(out)
+}
+
+pub fn solution1(data: &[String]) -> Result<u64> {
+    top_total(data, 1)
+}
+
+pub fn solution2(data: &[String]) -> Result<u64> {
+    top_total(data, 3)
 }
 
 #[cfg(test)]
@@ -42,11 +112,34 @@ mod tests {
 
     #[test]
     fn test_solution1() {
-        assert_eq!(24000, day1::solution1(&data()));
+        assert_eq!(24000, day1::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(45000, day1::solution2(&data()));
+        assert_eq!(45000, day1::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_top_total() {
+        assert_eq!(0, day1::top_total(&data(), 0).unwrap());
+        assert_eq!(45000, day1::top_total(&data(), 3).unwrap());
+        assert_eq!(55000, day1::top_total(&data(), 10).unwrap());
+    }
+
+    #[test]
+    fn test_report() {
+        assert_eq!(
+            "  elf  items    total
+    1      3     6000
+    2      1     4000
+    3      2    11000
+    4      3    24000
+    5      1    10000
+elves: 5, mean: 11000.0, median: 10000.0
+max gap: 13000 (between 11000 and 24000)
+",
+            day1::report(&data()).unwrap()
+        );
     }
 }
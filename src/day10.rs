@@ -1,3 +1,9 @@
+use crate::common::render::{Canvas, Rgb};
+use crate::simulation::Simulation;
+
+const CRT_WIDTH: usize = 40;
+const CRT_HEIGHT: usize = 6;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum Op {
     AddX(i32),
@@ -67,6 +73,58 @@ impl Device {
     }
 }
 
+/// The CRT drawing one pixel per cycle.
+pub struct Crt {
+    device: Device,
+    pixels: Vec<bool>,
+}
+
+impl Crt {
+    fn new(program: &[Op]) -> Self {
+        Self {
+            device: Device::new(program),
+            pixels: Vec::with_capacity(CRT_WIDTH * CRT_HEIGHT),
+        }
+    }
+}
+
+impl Simulation for Crt {
+    fn step(&mut self) -> bool {
+        if self.pixels.len() == CRT_WIDTH * CRT_HEIGHT {
+            return false;
+        }
+        self.pixels.push(self.device.is_pixel_drawn());
+        self.device.step();
+        true
+    }
+
+    fn frame(&self) -> Canvas<u8> {
+        let row = self.pixels.len() / CRT_WIDTH;
+        let cells = (0..CRT_WIDTH * CRT_HEIGHT)
+            .map(|i| match self.pixels.get(i) {
+                Some(true) => 2,
+                Some(false) => 1,
+                None if i / CRT_WIDTH == row
+                    && (i32::try_from(i % CRT_WIDTH).unwrap() - self.device.reg_x).abs() <= 1 =>
+                {
+                    3
+                }
+                None => 0,
+            })
+            .collect();
+        Canvas::new(CRT_WIDTH, CRT_HEIGHT, cells)
+    }
+
+    fn palette(&self) -> Vec<(char, Rgb)> {
+        vec![
+            (' ', [0, 0, 0]),
+            ('.', [30, 40, 30]),
+            ('#', [80, 255, 80]),
+            ('=', [40, 100, 40]),
+        ]
+    }
+}
+
 fn parse(data: &[String]) -> Vec<Op> {
     data.iter()
         .map(|x| {
@@ -91,23 +149,13 @@ pub fn solution1(data: &[String]) -> i32 {
 }
 
 pub fn solution2(data: &[String]) -> String {
-    let program = parse(data);
-    let mut device = Device::new(&program);
-    (0..240)
-        .map(|_| {
-            let result = if device.is_pixel_drawn() {
-                '#'
-            } else {
-                '.'
-            };
-            device.step();
-            result
-        })
-        .collect::<Vec<char>>()
-        .chunks(40)
-        .map(|arr| arr.iter().collect::<String>())
-        .collect::<Vec<_>>()
-        .join("\n")
+    let mut crt = simulation2(data);
+    while crt.step() {}
+    Canvas::new(CRT_WIDTH, CRT_HEIGHT, crt.pixels).ascii(|&x| if x { '#' } else { '.' })
+}
+
+pub fn simulation2(data: &[String]) -> Crt {
+    Crt::new(&parse(data))
 }
 
 #[cfg(test)]
@@ -1,7 +1,12 @@
-use gcd::Gcd;
+use std::ops::{Add, Mul};
+
+use color_eyre::eyre::{bail, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::common::math::{lcm_all, ModInt};
+use crate::common::parse::{parse_num, parse_nums, parse_paragraphs, Paragraph};
+
 #[derive(Debug, PartialEq)]
 enum WorryParam {
     Old,
@@ -30,7 +35,7 @@ struct Monkey<const V: u64> {
 }
 
 impl<const V: u64> Monkey<V> {
-    fn parse(data: &[String]) -> (usize, Self) {
+    fn parse(p: &Paragraph) -> Result<(usize, Self)> {
         lazy_static! {
             static ref RE_MONKEY: Regex = Regex::new(r"Monkey (\d+):").unwrap();
             static ref RE_ITEMS: Regex = Regex::new(r"Starting items: (.*)$").unwrap();
@@ -40,25 +45,18 @@ impl<const V: u64> Monkey<V> {
             static ref RE_TEST_CLAUSE: Regex =
                 Regex::new(r"If (true|false): throw to monkey (\d+)").unwrap();
         }
-        let idx: usize = RE_MONKEY.captures(&data[0]).unwrap()[1].parse().unwrap();
-        let items: Vec<u64> = RE_ITEMS.captures(&data[1]).unwrap()[1]
-            .split(", ")
-            .map(|x| x.parse().unwrap())
-            .collect();
-        let op_caps = RE_OPERATION.captures(&data[2]).unwrap();
+        let idx: usize = parse_num(&p.captures(0, &RE_MONKEY)?[1])?;
+        let items: Vec<u64> = parse_nums(&p.captures(1, &RE_ITEMS)?[1], ',')?;
+        let op_caps = p.captures(2, &RE_OPERATION)?;
         let worry_params = (
-            Self::parse_worry_param(&op_caps[1]),
-            Self::parse_worry_op(&op_caps[2]),
-            Self::parse_worry_param(&op_caps[3]),
+            Self::parse_worry_param(&op_caps[1])?,
+            Self::parse_worry_op(&op_caps[2])?,
+            Self::parse_worry_param(&op_caps[3])?,
         );
-        let divisible_by: u64 = RE_TEST.captures(&data[3]).unwrap()[1].parse().unwrap();
-        let if_true: usize = RE_TEST_CLAUSE.captures(&data[4]).unwrap()[2]
-            .parse()
-            .unwrap();
-        let if_false: usize = RE_TEST_CLAUSE.captures(&data[5]).unwrap()[2]
-            .parse()
-            .unwrap();
-        (
+        let divisible_by: u64 = parse_num(&p.captures(3, &RE_TEST)?[1])?;
+        let if_true: usize = parse_num(&p.captures(4, &RE_TEST_CLAUSE)?[2])?;
+        let if_false: usize = parse_num(&p.captures(5, &RE_TEST_CLAUSE)?[2])?;
+        Ok((
             idx,
             Monkey {
                 items,
@@ -69,25 +67,25 @@ impl<const V: u64> Monkey<V> {
                     if_false,
                 },
             },
-        )
+        ))
     }
 
-    fn parse_worry_op(s: &str) -> WorryOp {
-        match s {
+    fn parse_worry_op(s: &str) -> Result<WorryOp> {
+        Ok(match s {
             "*" => WorryOp::Mul,
             "+" => WorryOp::Add,
-            _ => panic!("unknown op '{s}'"),
-        }
+            _ => bail!("unknown op '{s}'"),
+        })
     }
 
-    fn parse_worry_param(s: &str) -> WorryParam {
-        match s {
+    fn parse_worry_param(s: &str) -> Result<WorryParam> {
+        Ok(match s {
             "old" => WorryParam::Old,
-            _ => WorryParam::Num(s.parse().unwrap()),
-        }
+            _ => WorryParam::Num(parse_num(s)?),
+        })
     }
 
-    fn worry_op(&self, old: u64) -> u64 {
+    fn worry_op(&self, old: u64, divisors_lcm: u64) -> u64 {
         let v1 = match self.worry_plan.0 {
             WorryParam::Num(x) => x,
             WorryParam::Old => old,
@@ -96,17 +94,23 @@ impl<const V: u64> Monkey<V> {
             WorryParam::Num(x) => x,
             WorryParam::Old => old,
         };
-        let result = match self.worry_plan.1 {
-            WorryOp::Add => v1 + v2,
-            WorryOp::Mul => v1 * v2,
-        };
         match V {
-            1 => result / 3,
-            2 => result,
+            1 => self.apply_op(v1, v2) / 3,
+            // only divisibility by the divisors matters, so it's safe to work modulo their lcm
+            2 => self
+                .apply_op(ModInt::new(v1, divisors_lcm), ModInt::new(v2, divisors_lcm))
+                .value(),
             _ => panic!("unknown version"),
         }
     }
 
+    fn apply_op<N: Add<Output = N> + Mul<Output = N>>(&self, v1: N, v2: N) -> N {
+        match self.worry_plan.1 {
+            WorryOp::Add => v1 + v2,
+            WorryOp::Mul => v1 * v2,
+        }
+    }
+
     fn throw_op(&self, n: u64) -> usize {
         if n % self.throw_plan.divisor == 0 {
             self.throw_plan.if_true
@@ -119,7 +123,7 @@ impl<const V: u64> Monkey<V> {
     fn turn_results(&self, divisors_lcm: u64) -> Vec<(usize, u64)> {
         self.items
             .iter()
-            .map(|&x| self.worry_op(x % divisors_lcm))
+            .map(|&x| self.worry_op(x, divisors_lcm))
             .map(|x| (self.throw_op(x), x))
             .collect()
     }
@@ -133,11 +137,8 @@ struct Monkeys<const V: u64> {
 
 impl<const V: u64> Monkeys<V> {
     fn new(v: Vec<Monkey<V>>) -> Self {
-        let divisors_lcm = v
-            .iter()
-            .map(|x| x.throw_plan.divisor)
-            .reduce(|acc, x| (acc * x) / acc.gcd(x))
-            .unwrap();
+        let divisors_lcm = lcm_all(v.iter().map(|x| x.throw_plan.divisor))
+            .expect("divisors lcm does not fit in u64");
         Self { v, divisors_lcm }
     }
 
@@ -158,18 +159,17 @@ impl<const V: u64> Monkeys<V> {
     }
 }
 
-fn parse<const V: u64>(input: &[String]) -> Monkeys<V> {
-    let monkey_data: Vec<_> = input.split(String::is_empty).collect();
+fn parse<const V: u64>(input: &[String]) -> Result<Monkeys<V>> {
+    let monkey_data = parse_paragraphs(input, Monkey::parse)?;
     let mut result = Vec::with_capacity(monkey_data.len());
-    for md in monkey_data {
-        let (idx, monkey) = Monkey::parse(md);
+    for (idx, monkey) in monkey_data {
         result.insert(idx, monkey);
     }
-    Monkeys::new(result)
+    Ok(Monkeys::new(result))
 }
 
-pub fn solution<const V: u64>(data: &[String], rounds: usize) -> usize {
-    let mut monkeys: Monkeys<V> = parse(data);
+pub fn solution<const V: u64>(data: &[String], rounds: usize) -> Result<usize> {
+    let mut monkeys: Monkeys<V> = parse(data)?;
     let mut result = vec![0; monkeys.v.len()];
 
     for _ in 0..rounds {
@@ -182,14 +182,14 @@ pub fn solution<const V: u64>(data: &[String], rounds: usize) -> usize {
 
     result.sort_unstable();
     result.reverse();
-    result[0] * result[1]
+    Ok(result[0] * result[1])
 }
 
-pub fn solution1(data: &[String]) -> usize {
+pub fn solution1(data: &[String]) -> Result<usize> {
     solution::<1>(data, 20)
 }
 
-pub fn solution2(data: &[String]) -> usize {
+pub fn solution2(data: &[String]) -> Result<usize> {
     solution::<2>(data, 10_000)
 }
 
@@ -231,11 +231,11 @@ Monkey 3:
 
     #[test]
     fn test_solution1() {
-        assert_eq!(10605, day11::solution1(&data()));
+        assert_eq!(10605, day11::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(2_713_310_158, day11::solution2(&data()));
+        assert_eq!(2_713_310_158, day11::solution2(&data()).unwrap());
     }
 }
@@ -1,18 +1,20 @@
 use std::cmp::Ordering;
 
+use color_eyre::eyre::{bail, Result};
 use json::JsonValue;
 
+use crate::common::parse::parse_paragraphs;
+
 type DataPair = (JsonValue, JsonValue);
 
-fn parse(data: &[String]) -> Vec<DataPair> {
-    data.split(String::is_empty)
-        .map(|v| {
-            v.iter()
-                .map(|x| json::parse(x).unwrap())
-                .collect::<Vec<_>>()
-        })
-        .map(|a| (a[0].clone(), a[1].clone()))
-        .collect()
+fn parse(data: &[String]) -> Result<Vec<DataPair>> {
+    parse_paragraphs(
+        data,
+        |p| match &p.parse_lines(|x| Ok(json::parse(x)?))?[..] {
+            [a, b] => Ok((a.clone(), b.clone())),
+            _ => bail!("expected a pair of packets"),
+        },
+    )
 }
 
 fn compare(v1: &JsonValue, v2: &JsonValue) -> Ordering {
@@ -46,18 +48,18 @@ fn compare(v1: &JsonValue, v2: &JsonValue) -> Ordering {
     }
 }
 
-pub fn solution1(input: &[String]) -> usize {
-    parse(input)
+pub fn solution1(input: &[String]) -> Result<usize> {
+    Ok(parse(input)?
         .iter()
         .map(|(a, b)| compare(a, b))
         .enumerate()
         .filter(|&(_, x)| x == Ordering::Less)
         .map(|(i, _)| i + 1)
-        .sum()
+        .sum())
 }
 
-pub fn solution2(input: &[String]) -> usize {
-    let mut data: Vec<_> = parse(input)
+pub fn solution2(input: &[String]) -> Result<usize> {
+    let mut data: Vec<_> = parse(input)?
         .iter()
         .flat_map(|(a, b)| vec![a.clone(), b.clone()])
         .collect();
@@ -68,7 +70,7 @@ pub fn solution2(input: &[String]) -> usize {
     data.sort_by(compare);
     let pos1 = data.iter().position(|x| *x == sep1).unwrap();
     let pos2 = data.iter().position(|x| *x == sep2).unwrap();
-    (pos1 + 1) * (pos2 + 1)
+    Ok((pos1 + 1) * (pos2 + 1))
 }
 
 #[cfg(test)]
@@ -105,11 +107,11 @@ mod tests {
 
     #[test]
     fn test_solution1() {
-        assert_eq!(13, day13::solution1(&data()));
+        assert_eq!(13, day13::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(140, day13::solution2(&data()));
+        assert_eq!(140, day13::solution2(&data()).unwrap());
     }
 }
@@ -1,9 +1,17 @@
 use std::{
     cmp::{max, min},
     collections::HashSet,
+    fmt::Display,
 };
 
-type Pos = (usize, usize);
+use color_eyre::eyre::{bail, Result};
+
+use crate::common::render::{Canvas, Rgb};
+use crate::simulation::Simulation;
+
+const SOURCE: Pos = (500, 0);
+
+type Pos = (i64, i64);
 
 struct Line(Pos, Pos);
 
@@ -28,23 +36,26 @@ impl Line {
 struct Grid {
     rock: HashSet<Pos>,
     sand: HashSet<Pos>,
-    max_depth: usize,
-    solid_bottom: Option<usize>,
+    max_depth: i64,
+    solid_bottom: Option<i64>,
 }
 
 impl Grid {
-    fn new(data: &[Line], solid_bottom_offset: Option<usize>) -> Self {
+    fn new(data: &[Line], solid_bottom_offset: Option<i64>) -> Result<Self> {
         let mut rock = HashSet::new();
         for line in data {
             rock.extend(line.positions());
         }
+        if rock.is_empty() {
+            bail!("the scan has no rock");
+        }
         let max_depth = Self::max_depth(&rock);
-        Self {
+        Ok(Self {
             rock,
             sand: HashSet::new(),
             max_depth,
             solid_bottom: solid_bottom_offset.map(|x| max_depth + x),
-        }
+        })
     }
 
     fn is_taken(&self, pos: &Pos) -> bool {
@@ -60,7 +71,7 @@ impl Grid {
     }
 
     /// Calculates where sand will land without actually adding it to the grid. None = will fall off the grid.
-    fn sand_land_position(&self, x: usize) -> Option<Pos> {
+    fn sand_land_position(&self, x: i64) -> Option<Pos> {
         let mut cur_pos = (x, 0);
         'falling: while !self.is_in_abyss(&cur_pos) {
             for next_pos in [
@@ -78,11 +89,24 @@ impl Grid {
         None
     }
 
-    fn max_depth(rock: &HashSet<Pos>) -> usize {
+    fn max_depth(rock: &HashSet<Pos>) -> i64 {
         rock.iter().map(|x| x.1).max().unwrap()
     }
 }
 
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let points = self
+            .rock
+            .iter()
+            .map(|&p| (p, '#'))
+            .chain(self.sand.iter().map(|&p| (p, 'o')))
+            .chain([(SOURCE, '+')]);
+        let (canvas, _) = Canvas::from_sparse(points, '.');
+        write!(f, "{}", canvas.ascii(|&c| c))
+    }
+}
+
 fn parse(input: &[String]) -> Vec<Line> {
     input
         .iter()
@@ -97,23 +121,88 @@ fn parse(input: &[String]) -> Vec<Line> {
         .collect()
 }
 
-pub fn solution1(input: &[String]) -> usize {
-    let mut grid = Grid::new(&parse(input), None);
-    while let Some(pos) = grid.sand_land_position(500) {
-        grid.sand.insert(pos);
+/// Sand pouring in one unit at a time until it falls off or blocks the source.
+pub struct Sand {
+    grid: Grid,
+    /// Top left and bottom right corners of everything that can get filled.
+    bounds: (Pos, Pos),
+}
+
+impl Sand {
+    fn new(grid: Grid) -> Self {
+        let bottom = grid.solid_bottom.unwrap_or(grid.max_depth);
+        let rock_x = grid.rock.iter().map(|p| p.0);
+        // a pile on the floor spreads as wide as it is high
+        let (pile_min, pile_max) = match grid.solid_bottom {
+            Some(b) => (SOURCE.0 - b, SOURCE.0 + b),
+            None => (SOURCE.0, SOURCE.0),
+        };
+        let min_x = rock_x.clone().fold(pile_min, min) - 1;
+        let max_x = rock_x.fold(pile_max, max) + 1;
+        Self {
+            grid,
+            bounds: ((min_x, 0), (max_x, bottom)),
+        }
     }
-    grid.sand.len()
 }
 
-pub fn solution2(input: &[String]) -> usize {
-    let mut grid = Grid::new(&parse(input), Some(2));
-    while let Some(pos) = grid.sand_land_position(500) {
-        grid.sand.insert(pos);
-        if pos == (500, 0) {
-            break;
+impl Simulation for Sand {
+    fn step(&mut self) -> bool {
+        if self.grid.sand.contains(&SOURCE) {
+            return false;
+        }
+        match self.grid.sand_land_position(SOURCE.0) {
+            Some(pos) => self.grid.sand.insert(pos),
+            None => false,
         }
     }
-    grid.sand.len()
+
+    fn frame(&self) -> Canvas<u8> {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds;
+        Canvas::from_rows((min_y..=max_y).map(|y| {
+            (min_x..=max_x).map(move |x| {
+                let pos = (x, y);
+                if self.grid.sand.contains(&pos) {
+                    2
+                } else if self.grid.is_taken(&pos) {
+                    1
+                } else if pos == SOURCE {
+                    3
+                } else {
+                    0
+                }
+            })
+        }))
+    }
+
+    fn palette(&self) -> Vec<(char, Rgb)> {
+        vec![
+            ('.', [20, 20, 30]),
+            ('#', [120, 110, 100]),
+            ('o', [230, 190, 90]),
+            ('+', [255, 255, 255]),
+        ]
+    }
+}
+
+pub fn solution1(input: &[String]) -> Result<usize> {
+    let mut sand = simulation1(input)?;
+    while sand.step() {}
+    Ok(sand.grid.sand.len())
+}
+
+pub fn solution2(input: &[String]) -> Result<usize> {
+    let mut sand = simulation2(input)?;
+    while sand.step() {}
+    Ok(sand.grid.sand.len())
+}
+
+pub fn simulation1(input: &[String]) -> Result<Sand> {
+    Ok(Sand::new(Grid::new(&parse(input), None)?))
+}
+
+pub fn simulation2(input: &[String]) -> Result<Sand> {
+    Ok(Sand::new(Grid::new(&parse(input), Some(2))?))
 }
 
 #[cfg(test)]
@@ -129,11 +218,47 @@ mod tests {
 
     #[test]
     fn test_solution1() {
-        assert_eq!(24, day14::solution1(&data()));
+        assert_eq!(24, day14::solution1(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_display() {
+        let mut grid = day14::Grid::new(&day14::parse(&data()), None).unwrap();
+        grid.sand.insert((500, 8));
+        assert_eq!(
+            r"......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+......o.#.
+#########.",
+            grid.to_string()
+        );
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(93, day14::solution2(&data()));
+        assert_eq!(93, day14::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_no_rock() {
+        for input in ["", "500,3"] {
+            let err = day14::solution2(&str2lines(input)).unwrap_err();
+            assert_eq!("the scan has no rock", err.to_string());
+        }
+    }
+
+    #[test]
+    fn test_floor_past_zero() {
+        // the pile on the floor spreads left of x = 0
+        let deep = str2lines("0,600 -> 1,600");
+        assert_eq!(0, day14::solution1(&deep).unwrap());
+        let sand = day14::simulation2(&deep).unwrap();
+        assert_eq!(((-103, 0), (1103, 602)), sand.bounds);
     }
 }
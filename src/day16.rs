@@ -7,6 +7,8 @@ use lazy_static::lazy_static;
 use rayon::prelude::*;
 use regex::Regex;
 
+use crate::common::bitset::BitSet64;
+
 #[derive(PartialEq, Eq, Hash, Clone, PartialOrd, Default, Copy)]
 struct Name(u16);
 
@@ -33,13 +35,14 @@ impl std::fmt::Debug for Name {
 #[derive(Debug)]
 struct Network<T> {
     valves: HashMap<T, u32>,
-    nonzero_valves: HashSet<T>,
+    /// Valves worth opening, indexed by their bit in `Path::opened_valves`.
+    nonzero_valves: Vec<T>,
     shortest_paths: HashMap<(T, T), u32>,
 }
 
 #[derive(Debug, Default, Clone)]
 struct Path<T> {
-    opened_valves: HashSet<T>,
+    opened_valves: BitSet64,
     pressure_per_minute: u32,
     current_valve: T,
     released_pressure: u32,
@@ -55,13 +58,28 @@ impl<T: PartialOrd + Eq + Hash + Copy + Display + std::fmt::Debug + Default> Net
         }
     }
 
-    fn nonzero_valves(valves: &HashMap<T, u32>) -> HashSet<T> {
+    fn nonzero_valves(valves: &HashMap<T, u32>) -> Vec<T> {
         valves
             .iter()
             .filter_map(|(&edge, &value)| (value != 0).then_some(edge))
             .collect()
     }
 
+    fn valve_bit(&self, valve: T) -> usize {
+        self.nonzero_valves
+            .iter()
+            .position(|&v| v == valve)
+            .unwrap()
+    }
+
+    /// Nonzero valves that are not in the `opened` set.
+    fn closed_valves(&self, opened: BitSet64) -> Vec<T> {
+        (BitSet64::full(self.nonzero_valves.len()) - opened)
+            .iter()
+            .map(|i| self.nonzero_valves[i])
+            .collect()
+    }
+
     /// Floyd-Warshall
     fn shortest_paths(valves: &HashMap<T, u32>, edges: &HashSet<(T, T)>) -> HashMap<(T, T), u32> {
         let mut dist: HashMap<(T, T), u32> = edges
@@ -89,7 +107,7 @@ impl<T: PartialOrd + Eq + Hash + Copy + Display + std::fmt::Debug + Default> Net
 
     fn all_paths_from(
         &self,
-        path_so_far: Path<T>,
+        path_so_far: &Path<T>,
         minutes: u32,
         eligible_valves: &[T],
     ) -> Vec<Path<T>> {
@@ -111,7 +129,7 @@ impl<T: PartialOrd + Eq + Hash + Copy + Display + std::fmt::Debug + Default> Net
             vec![Path {
                 released_pressure: path_so_far.released_pressure
                     + path_so_far.pressure_per_minute * minutes,
-                ..path_so_far
+                ..*path_so_far
             }]
         } else {
             valves_to_explore
@@ -121,14 +139,14 @@ impl<T: PartialOrd + Eq + Hash + Copy + Display + std::fmt::Debug + Default> Net
                         self.shortest_paths[&(path_so_far.current_valve, next_valve)] + 1;
                     let next_pressure = self.valves[&next_valve];
                     let mut next_path = path_so_far.clone();
-                    next_path.opened_valves.insert(next_valve);
+                    next_path.opened_valves.insert(self.valve_bit(next_valve));
                     next_path.pressure_per_minute += next_pressure;
                     next_path.current_valve = next_valve;
                     next_path.released_pressure += path_so_far.pressure_per_minute * minutes_added;
                     next_path.minutes_passed += minutes_added;
 
                     self.all_paths_from(
-                        next_path,
+                        &next_path,
                         minutes - minutes_added,
                         &valves_to_explore
                             .iter()
@@ -146,7 +164,7 @@ impl<T: PartialOrd + Eq + Hash + Copy + Display + std::fmt::Debug + Default> Net
             current_valve: start,
             ..Path::default()
         };
-        self.all_paths_from(init_path, minutes, eligible_valves)
+        self.all_paths_from(&init_path, minutes, eligible_valves)
     }
 }
 
@@ -180,24 +198,16 @@ fn max_pressure<T>(paths: &[Path<T>]) -> u32 {
 
 pub fn solution1(input: &[String]) -> u32 {
     let network = parse(input);
-    let paths = network.all_paths(
-        "AA".into(),
-        30,
-        &network.nonzero_valves.iter().copied().collect::<Vec<_>>(),
-    );
+    let paths = network.all_paths("AA".into(), 30, &network.nonzero_valves);
     max_pressure(&paths)
 }
 
 pub fn solution2(input: &[String]) -> u32 {
     let network = parse(input);
-    let paths = network.all_paths(
-        "AA".into(),
-        26,
-        &network.nonzero_valves.iter().copied().collect::<Vec<_>>(),
-    );
+    let paths = network.all_paths("AA".into(), 26, &network.nonzero_valves);
     let best_paths = paths
         .iter()
-        .group_by(|x| x.opened_valves.clone())
+        .group_by(|x| x.opened_valves)
         .into_iter()
         .map(|(k, vs)| (k, vs.map(|x| x.released_pressure).max().unwrap()))
         .collect::<Vec<_>>();
@@ -205,13 +215,7 @@ pub fn solution2(input: &[String]) -> u32 {
     best_paths
         .par_iter()
         .map(|(vs, p1)| {
-            let p2s = network.all_paths(
-                "AA".into(),
-                26,
-                &(&network.nonzero_valves - vs)
-                    .into_iter()
-                    .collect::<Vec<_>>(),
-            );
+            let p2s = network.all_paths("AA".into(), 26, &network.closed_valves(*vs));
             p1 + max_pressure(&p2s)
         })
         .max()
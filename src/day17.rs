@@ -1,3 +1,6 @@
+use crate::common::cycle::{extrapolate, find_cycle_brent};
+use crate::common::render::{Canvas, Rgb};
+use crate::simulation::Simulation;
 use lazy_static::lazy_static;
 use std::{
     fmt::Display,
@@ -7,6 +10,7 @@ use std::{
 
 const CHAMBER_WIDTH: u8 = 7;
 const BUFFER_HEIGHT: usize = 100;
+const VIEW_HEIGHT: usize = 30;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Dir {
@@ -131,31 +135,73 @@ impl Chamber {
     }
 }
 
-impl Display for Chamber {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in self.rocks.iter().rev() {
-            write!(f, "|")?;
-            for x in (0..CHAMBER_WIDTH).rev() {
-                let c = if (1 << x) & *row == 0 { "." } else { "#" };
-                write!(f, "{c}")?;
-            }
-            writeln!(f, "|")?;
-        }
-        write!(f, "|")?;
-        for _ in 0..CHAMBER_WIDTH {
-            write!(f, "-")?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tile {
+    Air,
+    Rock,
+    Wall,
+    Floor,
+}
+
+impl Tile {
+    fn glyph(self) -> char {
+        match self {
+            Tile::Air => '.',
+            Tile::Rock => '#',
+            Tile::Wall => '|',
+            Tile::Floor => '-',
         }
-        writeln!(f, "|")?;
-        Ok(())
     }
 }
 
-// fn determine_cycle()
+impl Chamber {
+    /// The chamber top to bottom, walls and floor included.
+    fn canvas(&self) -> Canvas<Tile> {
+        self.view(self.rocks.len() + 1)
+    }
+
+    /// The top `height` rows of the chamber, padded with air above while the tower
+    /// and floor are lower than that. Rows further down are never looked at.
+    fn view(&self, height: usize) -> Canvas<Tile> {
+        let walled = |tile: Tile| {
+            let mut tiles = vec![tile; usize::from(CHAMBER_WIDTH) + 2];
+            tiles[0] = Tile::Wall;
+            tiles[usize::from(CHAMBER_WIDTH) + 1] = Tile::Wall;
+            tiles
+        };
+        let rocks = self.rocks.iter().rev().take(height).map(|row| {
+            let mut tiles = walled(Tile::Air);
+            for x in 0..CHAMBER_WIDTH {
+                if (1 << (CHAMBER_WIDTH - 1 - x)) & *row != 0 {
+                    tiles[usize::from(x) + 1] = Tile::Rock;
+                }
+            }
+            tiles
+        });
+        let padding = height.saturating_sub(self.rocks.len() + 1);
+        let floor = (self.rocks.len() < height).then(|| walled(Tile::Floor));
+        Canvas::from_rows(
+            std::iter::repeat_with(|| walled(Tile::Air))
+                .take(padding)
+                .chain(rocks)
+                .chain(floor),
+        )
+    }
+}
+
+impl Display for Chamber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.canvas().ascii(|&t| t.glyph()))
+    }
+}
 
+#[derive(Clone)]
 struct Game {
     chamber: Chamber,
     instructions_iter: Cycle<Enumerate<IntoIter<Dir>>>,
     shapes_iter: Cycle<Enumerate<IntoIter<Vec<u8>>>>,
+    last_instruction: usize,
+    last_shape: usize,
 }
 
 impl Game {
@@ -165,15 +211,27 @@ impl Game {
             #[allow(clippy::unnecessary_to_owned)]
             instructions_iter: instructions.to_vec().into_iter().enumerate().cycle(),
             shapes_iter: SHAPES.clone().into_iter().enumerate().cycle(),
+            last_instruction: 0,
+            last_shape: 0,
         }
     }
 
+    /// Everything that determines how the next pieces will fall, give or take
+    /// a piece slipping below the top `BUFFER_HEIGHT` rows.
+    fn state_key(&self) -> (usize, usize, Vec<u8>) {
+        let rocks = &self.chamber.rocks;
+        let surface = rocks[rocks.len().saturating_sub(BUFFER_HEIGHT)..].to_vec();
+        (self.last_shape, self.last_instruction, surface)
+    }
+
     fn play_next_piece(&mut self) {
-        let (_, shape) = self.shapes_iter.next().unwrap();
+        let (shape_idx, shape) = self.shapes_iter.next().unwrap();
+        self.last_shape = shape_idx;
         let mut piece = Piece::new(&shape, self.chamber.height() + 3).pushed_n(Dir::Right, 2);
 
         loop {
-            let (_, instr) = self.instructions_iter.next().unwrap();
+            let (instr_idx, instr) = self.instructions_iter.next().unwrap();
+            self.last_instruction = instr_idx;
 
             // try pushing
             let new_piece = piece.pushed(instr);
@@ -204,6 +262,37 @@ impl Game {
     }
 }
 
+/// Rocks falling one at a time, watched through a window at the top of the tower.
+pub struct Tower {
+    game: Game,
+    rocks_left: usize,
+}
+
+impl Simulation for Tower {
+    fn step(&mut self) -> bool {
+        if self.rocks_left == 0 {
+            return false;
+        }
+        self.game.play_next_piece();
+        self.rocks_left -= 1;
+        true
+    }
+
+    fn frame(&self) -> Canvas<u8> {
+        self.game.chamber.view(VIEW_HEIGHT).map(|&t| t as u8)
+    }
+
+    fn palette(&self) -> Vec<(char, Rgb)> {
+        // in the same order as `Tile`
+        vec![
+            ('.', [20, 20, 30]),
+            ('#', [200, 120, 60]),
+            ('|', [150, 150, 150]),
+            ('-', [150, 150, 150]),
+        ]
+    }
+}
+
 fn parse(input: &str) -> Vec<Dir> {
     input
         .chars()
@@ -221,10 +310,35 @@ pub fn solution1(input: &[String]) -> usize {
     game.chamber.height()
 }
 
+fn tower(input: &[String], rocks: usize) -> Tower {
+    Tower {
+        game: Game::new(&parse(input[0].as_str())),
+        rocks_left: rocks,
+    }
+}
+
+pub fn simulation1(input: &[String]) -> Tower {
+    tower(input, 2022)
+}
+
+pub fn simulation2(input: &[String]) -> Tower {
+    tower(input, 1_000_000_000_000)
+}
+
 pub fn solution2(input: &[String]) -> usize {
-    let mut game = Game::new(&parse(input[0].as_str()));
-    game.play_n(1_000_000_000_000);
-    game.chamber.height()
+    let instructions = parse(input[0].as_str());
+    let cycle = find_cycle_brent(
+        Game::new(&instructions),
+        Game::state_key,
+        Game::play_next_piece,
+    );
+    extrapolate(
+        Game::new(&instructions),
+        Game::play_next_piece,
+        |game| game.chamber.height(),
+        cycle,
+        1_000_000_000_000,
+    )
 }
 
 #[cfg(test)]
@@ -280,6 +394,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display() {
+        let mut chamber = Chamber::new();
+        chamber.rocks = vec![0b0011_1100, 0b0000_1000];
+        assert_eq!("|...#...|\n|.####..|\n|-------|\n", chamber.to_string());
+    }
+
+    #[test]
+    fn test_view() {
+        let mut chamber = Chamber::new();
+        chamber.rocks = vec![0b0011_1100, 0b0000_1000];
+        let ascii = |height| chamber.view(height).ascii(|&t| t.glyph());
+        assert_eq!("|...#...|", ascii(1));
+        assert_eq!("|...#...|\n|.####..|", ascii(2));
+        assert_eq!("|.......|\n|...#...|\n|.####..|\n|-------|", ascii(4));
+    }
+
     #[test]
     fn test_play_n() {
         let instructions = parse(">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>");
@@ -298,7 +429,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_solution2() {
         assert_eq!(1_514_285_714_288, solution2(&data()));
     }
@@ -1,20 +1,26 @@
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Shape {
-    Rock,
-    Paper,
-    Scissors,
-}
+use std::collections::HashMap;
+use std::fmt::Write;
 
-impl Shape {
-    fn score(self) -> i32 {
-        match self {
-            Shape::Rock => 1,
-            Shape::Paper => 2,
-            Shape::Scissors => 3,
-        }
-    }
-}
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use json::JsonValue;
 
+use crate::common::bitset::BitSet64;
+
+/// Most readings of the response letters a report lists, every one of them is scored.
+const MAX_MAPPINGS: usize = 10_000;
+
+/// The classic game, the letters in the guide are filled in by each part.
+const ROCK_PAPER_SCISSORS: &str = r#"{
+    "shapes": [
+        {"name": "rock", "score": 1, "beats": ["scissors"]},
+        {"name": "paper", "score": 2, "beats": ["rock"]},
+        {"name": "scissors", "score": 3, "beats": ["paper"]}
+    ],
+    "outcomes": {"lose": 0, "draw": 3, "win": 6},
+    "opponent": {"A": "rock", "B": "paper", "C": "scissors"}
+}"#;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum MatchResult {
     Lose,
     Draw,
@@ -22,114 +28,353 @@ enum MatchResult {
 }
 
 impl MatchResult {
-    fn score(&self) -> i32 {
+    const ALL: [MatchResult; 3] = [MatchResult::Lose, MatchResult::Draw, MatchResult::Win];
+
+    fn name(self) -> &'static str {
         match self {
-            MatchResult::Lose => 0,
-            MatchResult::Draw => 3,
-            MatchResult::Win => 6,
+            MatchResult::Lose => "lose",
+            MatchResult::Draw => "draw",
+            MatchResult::Win => "win",
         }
     }
 }
 
+/// What the second letter of a guide line tells you to do.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Response {
+    Play(usize),
+    Reach(MatchResult),
+}
+
+/// A game where every shape beats some shapes and loses to all the others,
+/// together with how the strategy guide is written and scored.
+#[derive(Debug)]
+pub struct Rules {
+    names: Vec<String>,
+    shape_scores: Vec<i32>,
+    /// `beats[a]` holds every shape `a` wins against.
+    beats: Vec<BitSet64>,
+    /// Indexed by `MatchResult`.
+    result_scores: [i32; 3],
+    opponent: HashMap<char, usize>,
+    response: HashMap<char, Response>,
+}
+
+/// Shapes are played by index into `Rules`.
 struct Instruction {
-    opp: Shape,
-    you: Shape,
+    opp: usize,
+    you: usize,
 }
 
 impl Instruction {
-    fn parse_letters(s: &str) -> (char, char) {
-        let bs = s.as_bytes();
-        (bs[0] as char, bs[2] as char)
+    fn parse_letters(s: &str) -> Result<(char, char)> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next(), chars.next()) {
+            (Some(opp), Some(' '), Some(you), None) => Ok((opp, you)),
+            _ => bail!("expected two letters separated by a space, got '{s}'"),
+        }
+    }
+}
+
+fn letter(key: &str) -> Result<char> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => bail!("'{key}' is not a single letter"),
+    }
+}
+
+impl Rules {
+    pub fn parse(s: &str) -> Result<Self> {
+        Self::from_json(&json::parse(s)?)
     }
 
-    fn parse1(s: &str) -> Self {
-        let (opp, you) = Self::parse_letters(s);
-        Instruction {
-            opp: Self::parse_opp_letter(opp),
-            you: Self::parse_you_letter1(you),
+    fn from_json(v: &JsonValue) -> Result<Self> {
+        let shapes: Vec<_> = v["shapes"].members().collect();
+        if shapes.len() < 3 {
+            bail!("a game needs at least 3 shapes");
+        }
+        if shapes.len() > 64 {
+            bail!("a game can have at most 64 shapes");
+        }
+        let names = shapes
+            .iter()
+            .map(|s| {
+                s["name"]
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| eyre!("shape without a name: {s}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                bail!("shape '{name}' is defined twice");
+            }
+            if MatchResult::ALL.iter().any(|r| r.name() == name) {
+                bail!("'{name}' is an outcome and cannot be a shape");
+            }
+        }
+
+        let mut rules = Self {
+            shape_scores: vec![],
+            beats: vec![],
+            result_scores: [0; 3],
+            opponent: HashMap::new(),
+            response: HashMap::new(),
+            names,
+        };
+        for s in &shapes {
+            let name = s["name"].as_str().unwrap();
+            rules.shape_scores.push(
+                s["score"]
+                    .as_i32()
+                    .ok_or_else(|| eyre!("shape '{name}' needs a score"))?,
+            );
+            let beaten = s["beats"]
+                .members()
+                .map(|b| rules.shape(b.as_str().unwrap_or_default()))
+                .collect::<Result<_>>()
+                .wrap_err_with(|| format!("shape '{name}'"))?;
+            rules.beats.push(beaten);
+        }
+        rules.validate_cycle()?;
+
+        for r in MatchResult::ALL {
+            rules.result_scores[r as usize] = v["outcomes"][r.name()]
+                .as_i32()
+                .ok_or_else(|| eyre!("outcome '{}' needs a score", r.name()))?;
+        }
+        for (key, shape) in v["opponent"].entries() {
+            let shape = rules.shape(shape.as_str().unwrap_or_default())?;
+            rules.opponent.insert(letter(key)?, shape);
         }
+        for (key, meaning) in v["response"].entries() {
+            let meaning = meaning.as_str().unwrap_or_default();
+            let response = match MatchResult::ALL.iter().find(|r| r.name() == meaning) {
+                Some(&r) => Response::Reach(r),
+                None => Response::Play(rules.shape(meaning)?),
+            };
+            rules.response.insert(letter(key)?, response);
+        }
+        Ok(rules)
+    }
+
+    fn shape(&self, name: &str) -> Result<usize> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| eyre!("unknown shape '{name}'"))
+    }
+
+    /// Every two different shapes must have a winner, and no shape may beat or lose
+    /// to everything, or it would be the only sensible move.
+    fn validate_cycle(&self) -> Result<()> {
+        let n = self.names.len();
+        for a in 0..n {
+            if self.beats[a].contains(a) {
+                bail!("shape '{}' beats itself", self.names[a]);
+            }
+            for b in a + 1..n {
+                if self.beats[a].contains(b) == self.beats[b].contains(a) {
+                    bail!(
+                        "exactly one of '{}' and '{}' must beat the other",
+                        self.names[a],
+                        self.names[b]
+                    );
+                }
+            }
+            if self.beats[a].is_empty() || self.beats[a].len() == n - 1 {
+                bail!("shape '{}' breaks the cycle", self.names[a]);
+            }
+        }
+        Ok(())
     }
 
-    fn parse2(s: &str) -> Self {
-        let (opp, you) = Self::parse_letters(s);
-        let opp_shape = Self::parse_opp_letter(opp);
-        Instruction {
-            opp: opp_shape,
-            you: Self::parse_you_letter2(you, opp_shape),
+    fn result(&self, ins: &Instruction) -> MatchResult {
+        if ins.opp == ins.you {
+            MatchResult::Draw
+        } else if self.beats[ins.you].contains(ins.opp) {
+            MatchResult::Win
+        } else {
+            MatchResult::Lose
         }
     }
 
-    fn parse_opp_letter(c: char) -> Shape {
-        match c {
-            'A' => Shape::Rock,
-            'B' => Shape::Paper,
-            'C' => Shape::Scissors,
-            _ => panic!("unknown letter {c}"),
+    fn score(&self, ins: &Instruction) -> i32 {
+        self.result_scores[self.result(ins) as usize] + self.shape_scores[ins.you]
+    }
+
+    /// The best scoring shape that ends the round the way you were told to.
+    fn respond(&self, opp: usize, wanted: MatchResult) -> usize {
+        (0..self.names.len())
+            .filter(|&you| self.result(&Instruction { opp, you }) == wanted)
+            .max_by_key(|&you| (self.shape_scores[you], std::cmp::Reverse(you)))
+            .unwrap()
+    }
+
+    fn follow(&self, opp: usize, response: Response) -> Instruction {
+        let you = match response {
+            Response::Play(shape) => shape,
+            Response::Reach(result) => self.respond(opp, result),
+        };
+        Instruction { opp, you }
+    }
+
+    /// The opponent's shape and the still unread response letter.
+    fn parse_line(&self, s: &str) -> Result<(usize, char)> {
+        let (opp, you) = Instruction::parse_letters(s)?;
+        let opp = *self
+            .opponent
+            .get(&opp)
+            .ok_or_else(|| eyre!("unknown opponent letter '{opp}'"))?;
+        Ok((opp, you))
+    }
+
+    fn parse_guide(&self, data: &[String]) -> Result<Vec<(usize, char)>> {
+        data.iter()
+            .enumerate()
+            .map(|(i, s)| {
+                self.parse_line(s)
+                    .wrap_err_with(|| format!("line {}", i + 1))
+            })
+            .collect()
+    }
+
+    fn instruction(&self, s: &str) -> Result<Instruction> {
+        let (opp, you) = self.parse_line(s)?;
+        match self.response.get(&you) {
+            Some(&response) => Ok(self.follow(opp, response)),
+            None => bail!("unknown response letter '{you}'"),
         }
     }
 
-    fn parse_you_letter1(c: char) -> Shape {
-        match c {
-            'X' => Shape::Rock,
-            'Y' => Shape::Paper,
-            'Z' => Shape::Scissors,
-            _ => panic!("unknown letter {c}"),
+    /// Total score of following the whole guide.
+    pub fn play(&self, data: &[String]) -> Result<i32> {
+        data.iter()
+            .enumerate()
+            .map(|(i, s)| {
+                self.instruction(s)
+                    .map(|ins| self.score(&ins))
+                    .wrap_err_with(|| format!("line {}", i + 1))
+            })
+            .sum()
+    }
+
+    fn describe(&self, response: Response) -> &str {
+        match response {
+            Response::Play(shape) => &self.names[shape],
+            Response::Reach(result) => result.name(),
         }
     }
 
-    fn parse_you_letter2(c: char, opp: Shape) -> Shape {
-        match c {
-            'Y' => opp,
-            'X' => match opp {
-                Shape::Rock => Shape::Scissors,
-                Shape::Paper => Shape::Rock,
-                Shape::Scissors => Shape::Paper,
-            },
-            'Z' => match opp {
-                Shape::Rock => Shape::Paper,
-                Shape::Paper => Shape::Scissors,
-                Shape::Scissors => Shape::Rock,
-            },
-            _ => panic!("unknown letter {c}"),
+    /// Total score of the guide for every way of reading its response letters,
+    /// first as distinct shapes to play, then as distinct results to reach.
+    fn mapping_scores(&self, data: &[String]) -> Result<Vec<(Mapping, i32)>> {
+        let mut rounds: HashMap<(usize, char), i32> = HashMap::new();
+        for round in self.parse_guide(data)? {
+            *rounds.entry(round).or_default() += 1;
         }
+        let mut letters: Vec<char> = rounds.keys().map(|&(_, c)| c).collect();
+        letters.sort_unstable();
+        letters.dedup();
+        let k = letters.len();
+        let n = self.names.len();
+        if k > n {
+            bail!("{k} response letters are more than the {n} shapes");
+        }
+        // ways to give k letters distinct shapes, n * (n - 1) * ... * (n - k + 1)
+        let count = (n - k + 1..=n).try_fold(1_usize, usize::checked_mul);
+        if count.is_none_or(|c| c > MAX_MAPPINGS) {
+            bail!("{k} response letters can stand for {n} shapes in more than {MAX_MAPPINGS} ways");
+        }
+
+        let shapes: Vec<_> = (0..self.names.len()).map(Response::Play).collect();
+        let results: Vec<_> = MatchResult::ALL.into_iter().map(Response::Reach).collect();
+        let mut scores = vec![];
+        for meanings in arrangements(&shapes, k)
+            .into_iter()
+            .chain(arrangements(&results, k))
+        {
+            let mapping: Mapping = letters.iter().copied().zip(meanings).collect();
+            let score = rounds
+                .iter()
+                .map(|(&(opp, c), &count)| {
+                    let response = mapping.iter().find(|m| m.0 == c).unwrap().1;
+                    count * self.score(&self.follow(opp, response))
+                })
+                .sum();
+            scores.push((mapping, score));
+        }
+        Ok(scores)
     }
 
-    fn result(&self) -> MatchResult {
-        match self {
-            _ if self.opp == self.you => MatchResult::Draw,
-            Instruction {
-                opp: Shape::Paper,
-                you: Shape::Scissors,
-            }
-            | Instruction {
-                opp: Shape::Rock,
-                you: Shape::Paper,
+    /// Scores of every reading of the guide and which readings score best and worst.
+    pub fn mapping_report(&self, data: &[String]) -> Result<String> {
+        let scores = self.mapping_scores(data)?;
+        let show = |mapping: &Mapping| {
+            mapping
+                .iter()
+                .map(|&(c, r)| format!("{c}={}", self.describe(r)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut out = String::new();
+        for (mapping, score) in &scores {
+            writeln!(out, "{}: {score}", show(mapping))?;
+        }
+        let extremes = [
+            ("max", scores.iter().map(|s| s.1).max()),
+            ("min", scores.iter().map(|s| s.1).min()),
+        ];
+        for (label, best) in extremes {
+            let Some(best) = best else { continue };
+            for (mapping, _) in scores.iter().filter(|s| s.1 == best) {
+                writeln!(out, "{label} {best}: {}", show(mapping))?;
             }
-            | Instruction {
-                opp: Shape::Scissors,
-                you: Shape::Rock,
-            } => MatchResult::Win,
-            _ => MatchResult::Lose,
         }
+        Ok(out)
     }
+}
 
-    fn score(&self) -> i32 {
-        self.result().score() + self.you.score()
+/// Response letters of a guide, each with what it is taken to mean.
+type Mapping = Vec<(char, Response)>;
+
+/// All ways to pick `k` different items in order.
+fn arrangements<T: Copy>(items: &[T], k: usize) -> Vec<Vec<T>> {
+    if k > items.len() {
+        return vec![];
+    }
+    if k == 0 {
+        return vec![vec![]];
     }
+    let mut result = vec![];
+    for (i, &item) in items.iter().enumerate() {
+        let rest: Vec<T> = items[..i].iter().chain(&items[i + 1..]).copied().collect();
+        for mut tail in arrangements(&rest, k - 1) {
+            tail.insert(0, item);
+            result.push(tail);
+        }
+    }
+    result
+}
+
+/// The puzzle's own game with whichever letters the guide uses.
+pub fn report(data: &[String]) -> Result<String> {
+    preset(json::object! {}).mapping_report(data)
+}
+
+fn preset(response: JsonValue) -> Rules {
+    let mut v = json::parse(ROCK_PAPER_SCISSORS).unwrap();
+    v["response"] = response;
+    Rules::from_json(&v).unwrap()
 }
 
-pub fn solution1(data: &[String]) -> i32 {
-    data.iter()
-        .map(|s| Instruction::parse1(s))
-        .map(|x| x.score())
-        .sum()
+pub fn solution1(data: &[String]) -> Result<i32> {
+    preset(json::object! {X: "rock", Y: "paper", Z: "scissors"}).play(data)
 }
 
-pub fn solution2(data: &[String]) -> i32 {
-    data.iter()
-        .map(|s| Instruction::parse2(s))
-        .map(|x| x.score())
-        .sum()
+pub fn solution2(data: &[String]) -> Result<i32> {
+    preset(json::object! {X: "lose", Y: "draw", Z: "win"}).play(data)
 }
 
 #[cfg(test)]
@@ -144,13 +389,101 @@ C Z"#,
         )
     }
 
+    const RPSLS: &str = include_str!("../data/rules2_rpsls.json");
+
+    /// The shipped rules with two more letters that play a shape.
+    fn rpsls() -> day2::Rules {
+        let mut v = json::parse(RPSLS).unwrap();
+        v["response"]["V"] = "rock".into();
+        v["response"]["W"] = "spock".into();
+        day2::Rules::from_json(&v).unwrap()
+    }
+
     #[test]
     fn test_solution1() {
-        assert_eq!(15, day2::solution1(&data()));
+        assert_eq!(15, day2::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(12, day2::solution2(&data()));
+        assert_eq!(12, day2::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_rpsls() {
+        let rules = rpsls();
+        // spock beats scissors, lizard loses to rock, the best loss to paper is spock
+        let data = str2lines("C W\nD V\nB X\nE Y");
+        assert_eq!(6 + 5 + 6 + 1 + 5 + 3 + 5, rules.play(&data).unwrap());
+    }
+
+    #[test]
+    fn test_report() {
+        let report = day2::report(&data()).unwrap();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(14, lines.len());
+        assert_eq!("X=rock Y=paper Z=scissors: 15", lines[0]);
+        assert_eq!("X=lose Y=draw Z=win: 12", lines[6]);
+        assert_eq!(
+            vec![
+                "max 24: X=scissors Y=paper Z=rock",
+                "min 6: X=rock Y=scissors Z=paper"
+            ],
+            lines[12..]
+        );
+    }
+
+    #[test]
+    fn test_bad_rules() {
+        let not_cyclic = RPSLS.replace(r#""scissors", "lizard"]},"#, r#""scissors"]},"#);
+        assert_ne!(RPSLS, not_cyclic);
+        let err = day2::Rules::parse(&not_cyclic).unwrap_err();
+        assert_eq!(
+            "exactly one of 'rock' and 'lizard' must beat the other",
+            err.to_string()
+        );
+        let rules = rpsls();
+        let err = rules.play(&str2lines("A V\nF V")).unwrap_err();
+        assert_eq!("unknown opponent letter 'F'", err.root_cause().to_string());
+    }
+
+    #[test]
+    fn test_too_many_mappings() {
+        // 9 shapes each beating the next 4 around the circle
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h", "i"];
+        let shapes: Vec<_> = (0..9)
+            .map(|i| {
+                let beats: Vec<_> = (1..=4).map(|d| names[(i + d) % 9]).collect();
+                json::object! {name: names[i], score: 1, beats: beats}
+            })
+            .collect();
+        let rules = json::object! {
+            shapes: shapes,
+            outcomes: {lose: 0, draw: 3, win: 6},
+            opponent: {A: "a"},
+        };
+        let rules = day2::Rules::parse(&rules.dump()).unwrap();
+        assert_eq!(
+            9 * 8 * 7 * 6,
+            rules
+                .mapping_report(&str2lines("A S\nA T\nA U\nA V"))
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("S="))
+                .count()
+        );
+        let err = rules
+            .mapping_report(&str2lines("A S\nA T\nA U\nA V\nA W"))
+            .unwrap_err();
+        assert_eq!(
+            "5 response letters can stand for 9 shapes in more than 10000 ways",
+            err.to_string()
+        );
+        let err = day2::report(&str2lines("A W\nA X\nA Y\nA Z")).unwrap_err();
+        assert_eq!(
+            "4 response letters are more than the 3 shapes",
+            err.to_string()
+        );
+        assert!(day2::arrangements(&[0; 12], 13).is_empty());
     }
 }
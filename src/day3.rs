@@ -1,65 +1,151 @@
-use std::collections::HashSet;
+use std::fmt::Write;
 
+use color_eyre::eyre::{bail, Result, WrapErr};
+
+use crate::common::bitset::BitSet64;
+
+/// Items are stored by their priority, 1 to 52.
 struct Backpack {
-    c1: HashSet<u8>,
-    c2: HashSet<u8>,
+    c1: BitSet64,
+    c2: BitSet64,
 }
 
 impl Backpack {
-    fn str2items(s: &str) -> HashSet<u8> {
-        s.bytes().collect()
+    fn str2items(s: &str) -> Result<BitSet64> {
+        s.bytes().map(|x| Ok(usize::from(priority(x)?))).collect()
     }
 
-    fn parse(s: &str) -> Self {
-        let (c1, c2) = s.split_at(s.len() / 2);
-        Backpack {
-            c1: Self::str2items(c1),
-            c2: Self::str2items(c2),
+    fn parse(s: &str) -> Result<Self> {
+        if !s.len().is_multiple_of(2) {
+            bail!("odd number of items in '{s}'");
         }
+        let (c1, c2) = s.split_at(s.len() / 2);
+        Ok(Backpack {
+            c1: Self::str2items(c1)?,
+            c2: Self::str2items(c2)?,
+        })
     }
 
-    fn priority(item: u8) -> u8 {
-        if item.is_ascii_lowercase() {
-            item - b'a' + 1
-        } else {
-            item - b'A' + 27
-        }
+    fn items(&self) -> BitSet64 {
+        self.c1 | self.c2
     }
 
-    fn common_items(&self) -> HashSet<u8> {
-        self.c1.intersection(&self.c2).copied().collect()
+    fn common_items(&self) -> BitSet64 {
+        self.c1 & self.c2
     }
 
     fn total_common_priorities(&self) -> i32 {
-        self.common_items()
-            .iter()
-            .map(|x| i32::from(Self::priority(*x)))
-            .sum()
+        total(self.common_items())
+    }
+}
+
+fn priority(item: u8) -> Result<u8> {
+    match item {
+        b'a'..=b'z' => Ok(item - b'a' + 1),
+        b'A'..=b'Z' => Ok(item - b'A' + 27),
+        _ => bail!("'{}' is not an item", char::from(item)),
+    }
+}
+
+fn item(priority: usize) -> char {
+    let p = u8::try_from(priority).unwrap();
+    char::from(if p <= 26 { b'a' + p - 1 } else { b'A' + p - 27 })
+}
+
+fn total(items: BitSet64) -> i32 {
+    items.iter().map(|x| i32::try_from(x).unwrap()).sum()
+}
+
+/// Items with their priorities, e.g. `p(16) L(38)`.
+fn describe(items: BitSet64) -> String {
+    let described: Vec<_> = items.iter().map(|p| format!("{}({p})", item(p))).collect();
+    if described.is_empty() {
+        "none".into()
+    } else {
+        described.join(" ")
     }
 }
 
-pub fn solution1(data: &[String]) -> i32 {
+fn parse(data: &[String]) -> Result<Vec<Backpack>> {
     data.iter()
-        .map(|x| Backpack::parse(x))
-        .map(|x| x.total_common_priorities())
-        .sum()
+        .enumerate()
+        .map(|(i, s)| Backpack::parse(s).wrap_err_with(|| format!("line {}", i + 1)))
+        .collect()
 }
 
-pub fn solution2(data: &[String]) -> i32 {
-    // to hell with that
-    data.chunks(3)
-        .map(|x| {
-            let sets: Vec<HashSet<u8>> = x.iter().map(|i| Backpack::str2items(i)).collect();
-            let s1 = sets.get(0).unwrap();
-            let s2 = sets.get(1).unwrap();
-            let s3 = sets.get(2).unwrap();
-            Backpack {
-                c1: s1.intersection(s2).copied().collect(),
-                c2: s3.clone(),
-            }
-        })
-        .map(|x| x.total_common_priorities())
-        .sum()
+/// Splits the backpacks into groups of `k` elves.
+fn groups(backpacks: &[Backpack], k: usize) -> Result<std::slice::Chunks<'_, Backpack>> {
+    if k == 0 || !backpacks.len().is_multiple_of(k) {
+        bail!(
+            "{} backpacks cannot be split into groups of {k}",
+            backpacks.len()
+        );
+    }
+    Ok(backpacks.chunks(k))
+}
+
+/// Items every backpack in the group has, which should be just the badge.
+fn badge(group: &[Backpack]) -> BitSet64 {
+    group
+        .iter()
+        .map(Backpack::items)
+        .reduce(|a, b| a & b)
+        .unwrap_or_default()
+}
+
+fn check_badge(badge: BitSet64, number: usize, k: usize) -> Result<()> {
+    let lines = format!("lines {}-{}", (number - 1) * k + 1, number * k);
+    match badge.len() {
+        1 => Ok(()),
+        0 => bail!("group {number} ({lines}) has no badge"),
+        _ => bail!(
+            "group {number} ({lines}) has more than one badge: {}",
+            describe(badge)
+        ),
+    }
+}
+
+/// Sum of the badge priorities of groups of `k` elves.
+pub fn badge_total(data: &[String], k: usize) -> Result<i32> {
+    let backpacks = parse(data)?;
+    let mut sum = 0;
+    for (i, group) in groups(&backpacks, k)?.enumerate() {
+        let badge = badge(group);
+        check_badge(badge, i + 1, k)?;
+        sum += total(badge);
+    }
+    Ok(sum)
+}
+
+/// Each backpack's duplicated items and each group's badge, for groups of `k` elves.
+/// Groups without exactly one badge are listed rather than rejected.
+pub fn report(data: &[String], k: usize) -> Result<String> {
+    let backpacks = parse(data)?;
+    let mut out = String::new();
+    for (i, group) in groups(&backpacks, k)?.enumerate() {
+        let badge = badge(group);
+        writeln!(out, "group {}: badge {}", i + 1, describe(badge))?;
+        for (j, backpack) in group.iter().enumerate() {
+            writeln!(
+                out,
+                "  line {}: duplicated {}",
+                i * k + j + 1,
+                describe(backpack.common_items())
+            )?;
+        }
+    }
+    Ok(out)
+}
+
+pub fn solution1(data: &[String]) -> Result<i32> {
+    Ok(parse(data)?
+        .iter()
+        .map(Backpack::total_common_priorities)
+        .sum())
+}
+
+pub fn solution2(data: &[String]) -> Result<i32> {
+    badge_total(data, 3)
 }
 
 #[cfg(test)]
@@ -79,11 +165,53 @@ CrZsJsPPZsGzwwsLwLmpwMDw"#,
 
     #[test]
     fn test_solution1() {
-        assert_eq!(157, day3::solution1(&data()));
+        assert_eq!(157, day3::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(70, day3::solution2(&data()));
+        assert_eq!(70, day3::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        let err = day3::badge_total(&data(), 2).unwrap_err();
+        assert_eq!(
+            "group 1 (lines 1-2) has more than one badge: f(6) r(18) s(19) F(32) M(39)",
+            err.to_string()
+        );
+        let err = day3::badge_total(&str2lines("ab\ncd"), 2).unwrap_err();
+        assert_eq!("group 1 (lines 1-2) has no badge", err.to_string());
+        assert_eq!(
+            "6 backpacks cannot be split into groups of 4",
+            day3::badge_total(&data(), 4).unwrap_err().to_string()
+        );
+        assert_eq!(18, day3::badge_total(&data()[..3], 3).unwrap());
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        let err = day3::solution1(&str2lines("abca\nabc")).unwrap_err();
+        assert_eq!("line 2", err.to_string());
+        assert_eq!("odd number of items in 'abc'", err.root_cause().to_string());
+        let err = day3::solution1(&str2lines("a1")).unwrap_err();
+        assert_eq!("'1' is not an item", err.root_cause().to_string());
+    }
+
+    #[test]
+    fn test_report() {
+        let report = day3::report(&data(), 3).unwrap();
+        assert_eq!(
+            "group 1: badge r(18)
+  line 1: duplicated p(16)
+  line 2: duplicated L(38)
+  line 3: duplicated P(42)
+group 2: badge Z(52)
+  line 4: duplicated v(22)
+  line 5: duplicated t(20)
+  line 6: duplicated s(19)
+",
+            report
+        );
     }
 }
@@ -1,7 +1,109 @@
+use color_eyre::eyre::Result;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt::Write;
 use std::ops::RangeInclusive;
 
+/// How two ranges of sections lie relative to each other, after Allen's interval
+/// algebra. Sections are whole, so `2-3` meets `4-5` and `2-3` overlaps `3-5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relation {
+    Before,
+    Meets,
+    Overlaps,
+    Starts,
+    During,
+    Finishes,
+    Equals,
+    FinishedBy,
+    Contains,
+    StartedBy,
+    OverlappedBy,
+    MetBy,
+    After,
+}
+
+impl Relation {
+    const ALL: [Relation; 13] = [
+        Relation::Before,
+        Relation::Meets,
+        Relation::Overlaps,
+        Relation::Starts,
+        Relation::During,
+        Relation::Finishes,
+        Relation::Equals,
+        Relation::FinishedBy,
+        Relation::Contains,
+        Relation::StartedBy,
+        Relation::OverlappedBy,
+        Relation::MetBy,
+        Relation::After,
+    ];
+
+    /// Relation of `a` to `b`.
+    fn classify(a: &RangeInclusive<i32>, b: &RangeInclusive<i32>) -> Self {
+        // as half-open ranges, so that adjacent sections meet
+        let (s1, e1) = (*a.start(), a.end() + 1);
+        let (s2, e2) = (*b.start(), b.end() + 1);
+        match (s1.cmp(&s2), e1.cmp(&e2)) {
+            _ if e1 < s2 => Relation::Before,
+            _ if e1 == s2 => Relation::Meets,
+            _ if e2 < s1 => Relation::After,
+            _ if e2 == s1 => Relation::MetBy,
+            (Ordering::Equal, Ordering::Equal) => Relation::Equals,
+            (Ordering::Equal, Ordering::Less) => Relation::Starts,
+            (Ordering::Equal, Ordering::Greater) => Relation::StartedBy,
+            (Ordering::Greater, Ordering::Equal) => Relation::Finishes,
+            (Ordering::Less, Ordering::Equal) => Relation::FinishedBy,
+            (Ordering::Greater, Ordering::Less) => Relation::During,
+            (Ordering::Less, Ordering::Greater) => Relation::Contains,
+            (Ordering::Less, Ordering::Less) => Relation::Overlaps,
+            (Ordering::Greater, Ordering::Greater) => Relation::OverlappedBy,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Relation::Before => "before",
+            Relation::Meets => "meets",
+            Relation::Overlaps => "overlaps",
+            Relation::Starts => "starts",
+            Relation::During => "during",
+            Relation::Finishes => "finishes",
+            Relation::Equals => "equals",
+            Relation::FinishedBy => "finished by",
+            Relation::Contains => "contains",
+            Relation::StartedBy => "started by",
+            Relation::OverlappedBy => "overlapped by",
+            Relation::MetBy => "met by",
+            Relation::After => "after",
+        }
+    }
+
+    /// One of the ranges lies completely within the other.
+    fn is_nested(self) -> bool {
+        matches!(
+            self,
+            Relation::Starts
+                | Relation::During
+                | Relation::Finishes
+                | Relation::Equals
+                | Relation::FinishedBy
+                | Relation::Contains
+                | Relation::StartedBy
+        )
+    }
+
+    /// The ranges have at least one section in common.
+    fn shares_sections(self) -> bool {
+        !matches!(
+            self,
+            Relation::Before | Relation::Meets | Relation::MetBy | Relation::After
+        )
+    }
+}
+
 struct Assignment {
     e1: RangeInclusive<i32>,
     e2: RangeInclusive<i32>,
@@ -19,14 +121,17 @@ impl Assignment {
         }
     }
 
+    /// Relation of the first elf's sections to the second's.
+    fn relation(&self) -> Relation {
+        Relation::classify(&self.e1, &self.e2)
+    }
+
     fn has_full_overlap(&self) -> bool {
-        (self.e1.start() <= self.e2.start() && self.e1.end() >= self.e2.end())
-            || (self.e2.start() <= self.e1.start() && self.e2.end() >= self.e1.end())
+        self.relation().is_nested()
     }
 
     fn has_overlap(&self) -> bool {
-        (self.e1.start() <= self.e2.start() && self.e1.end() >= self.e2.start())
-            || (self.e2.start() <= self.e1.start() && self.e2.end() >= self.e1.start())
+        self.relation().shares_sections()
     }
 }
 
@@ -44,8 +149,91 @@ pub fn solution2(data: &[String]) -> usize {
         .count()
 }
 
+/// Sections from the first assigned to the last, split into runs where the same
+/// number of elves cover them. Found with a sweep over range starts and ends.
+fn coverage(assignments: &[Assignment]) -> Vec<(RangeInclusive<i32>, usize)> {
+    let mut events: Vec<(i32, isize)> = assignments
+        .iter()
+        .flat_map(|a| [&a.e1, &a.e2])
+        .filter(|r| !r.is_empty())
+        .flat_map(|r| [(*r.start(), 1), (r.end() + 1, -1)])
+        .collect();
+    events.sort_unstable();
+
+    let mut runs = vec![];
+    let mut count = 0;
+    for (i, &(section, change)) in events.iter().enumerate() {
+        count += change;
+        match events.get(i + 1) {
+            Some(&(next, _)) if next > section => {
+                runs.push((section..=next - 1, count.unsigned_abs()));
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Adjacent runs joined together.
+fn merged(runs: impl IntoIterator<Item = RangeInclusive<i32>>) -> Vec<RangeInclusive<i32>> {
+    let mut result: Vec<RangeInclusive<i32>> = vec![];
+    for run in runs {
+        match result.last_mut() {
+            Some(last) if last.end() + 1 == *run.start() => *last = *last.start()..=*run.end(),
+            _ => result.push(run),
+        }
+    }
+    result
+}
+
+fn show(runs: &[RangeInclusive<i32>]) -> String {
+    if runs.is_empty() {
+        return "none".into();
+    }
+    runs.iter()
+        .map(|r| {
+            if r.start() == r.end() {
+                r.start().to_string()
+            } else {
+                format!("{}-{}", r.start(), r.end())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// How many pairs fall into each relation, then how the elves cover the sections,
+/// listing those covered by more than `more_than` elves if given.
+pub fn report(data: &[String], more_than: Option<usize>) -> Result<String> {
+    let assignments: Vec<_> = data.iter().map(|x| Assignment::parse(x)).collect();
+    let mut out = String::new();
+    for relation in Relation::ALL {
+        let count = assignments
+            .iter()
+            .filter(|a| a.relation() == relation)
+            .count();
+        writeln!(out, "{:<13} {count}", relation.name())?;
+    }
+
+    let runs = coverage(&assignments);
+    let max = runs.iter().map(|r| r.1).max().unwrap_or(0);
+    let covered_by = |pred: &dyn Fn(usize) -> bool| {
+        show(&merged(
+            runs.iter().filter(|r| pred(r.1)).map(|r| r.0.clone()),
+        ))
+    };
+    writeln!(out)?;
+    writeln!(out, "max coverage: {max} at {}", covered_by(&|c| c == max))?;
+    writeln!(out, "uncovered: {}", covered_by(&|c| c == 0))?;
+    if let Some(n) = more_than {
+        writeln!(out, "covered by more than {n}: {}", covered_by(&|c| c > n))?;
+    }
+    Ok(out)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{coverage, merged, Assignment, Relation};
     use crate::{common::str2lines, day4};
 
     fn data() -> Vec<String> {
@@ -59,6 +247,68 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_relations() {
+        let cases = [
+            ("1-2,4-5", Relation::Before),
+            ("1-3,4-5", Relation::Meets),
+            ("1-4,4-5", Relation::Overlaps),
+            ("4-4,4-5", Relation::Starts),
+            ("4-4,3-5", Relation::During),
+            ("5-5,3-5", Relation::Finishes),
+            ("3-5,3-5", Relation::Equals),
+            ("3-5,5-5", Relation::FinishedBy),
+            ("3-5,4-4", Relation::Contains),
+            ("3-5,3-4", Relation::StartedBy),
+            ("4-6,3-5", Relation::OverlappedBy),
+            ("6-7,3-5", Relation::MetBy),
+            ("7-7,3-5", Relation::After),
+        ];
+        for (s, relation) in cases {
+            assert_eq!(relation, Assignment::parse(s).relation(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = day4::report(&data(), Some(5)).unwrap();
+        let counts: Vec<_> = report.lines().filter(|l| !l.ends_with(" 0")).collect();
+        assert_eq!(
+            vec![
+                "before        1",
+                "meets         1",
+                "overlaps      2",
+                "finishes      1",
+                "contains      1",
+                "",
+                "max coverage: 8 at 6",
+                "uncovered: none",
+                "covered by more than 5: 4-7"
+            ],
+            counts
+        );
+    }
+
+    #[test]
+    fn test_coverage() {
+        let assignments: Vec<_> = ["1-2,6-6", "2-3,6-7"]
+            .iter()
+            .map(|s| Assignment::parse(s))
+            .collect();
+        assert_eq!(
+            vec![
+                (1..=1, 1),
+                (2..=2, 2),
+                (3..=3, 1),
+                (4..=5, 0),
+                (6..=6, 2),
+                (7..=7, 1)
+            ],
+            coverage(&assignments)
+        );
+        assert_eq!(vec![1..=3, 6..=7], merged([1..=1, 2..=3, 6..=7]));
+    }
+
     #[test]
     fn test_solution1() {
         assert_eq!(2, day4::solution1(&data()));
@@ -1,96 +1,450 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fmt::{Display, Write};
+use std::hash::{Hash, Hasher};
+
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
 use lazy_static::lazy_static;
 use regex::Regex;
 
-#[derive(Debug)]
-struct Instruction {
+use crate::common::parse::{paragraphs, parse_num};
+
+/// How many stack layouts the planner looks at before giving up. Only a
+/// fingerprint and a back link are kept for each, a layout is replayed from
+/// the start when its turn comes.
+const MAX_PLAN_STATES: usize = 500_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
     amount: usize,
     from: usize,
     to: usize,
 }
 
 impl Instruction {
-    fn parse(s: &str) -> Self {
+    fn parse(s: &str) -> Result<Self> {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"^move (\d+) from (\d+) to (\d+)$").unwrap();
         }
-        let caps = RE.captures(s).unwrap();
-        Self {
-            amount: caps[1].parse().unwrap(),
-            from: caps[2].parse().unwrap(),
-            to: caps[3].parse().unwrap(),
+        let caps = RE.captures(s).ok_or_else(|| eyre!("not an instruction"))?;
+        Ok(Self {
+            amount: parse_num(&caps[1])?,
+            from: parse_num(&caps[2])?,
+            to: parse_num(&caps[3])?,
+        })
+    }
+
+    /// Checks that the instruction can be carried out on these stacks.
+    fn validate(&self, crates: &Crates) -> Result<()> {
+        for stack in [self.from, self.to] {
+            if stack == 0 || stack > crates.0.len() {
+                bail!("there is no stack {stack}");
+            }
+        }
+        let available = crates.0[self.from - 1].len();
+        if self.amount > available {
+            bail!("stack {} has only {available} crates", self.from);
         }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-struct Crates(Vec<Vec<char>>);
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
 
-impl Crates {
-    fn parse(data: &[String]) -> Self {
-        let mut iter = data.iter().rev();
-        let ncol = iter.next().unwrap().split_whitespace().count();
-        let mut crates = Self(Vec::new());
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Crates(Vec<Vec<char>>);
 
-        for _ in 0..ncol {
-            crates.0.push(Vec::new());
+impl Crates {
+    fn parse(data: &[String]) -> Result<Self> {
+        let Some((labels, rows)) = data.split_last() else {
+            bail!("the crate diagram is empty");
+        };
+        for (i, label) in labels.split_whitespace().enumerate() {
+            if parse_num::<usize>(label).ok() != Some(i + 1) {
+                bail!("stack label '{label}' should be {}", i + 1);
+            }
         }
+        let mut crates = Self(vec![vec![]; labels.split_whitespace().count()]);
 
-        for s in iter {
+        for (row, s) in rows.iter().enumerate().rev() {
+            let line = row + 1;
             for (i, c) in s.as_bytes().chunks(4).enumerate() {
-                let letter = c[1] as char;
-                if letter != ' ' {
-                    crates.0[i].push(letter);
+                match c {
+                    [b'[', letter, b']'] | [b'[', letter, b']', b' '] => {
+                        let Some(stack) = crates.0.get_mut(i) else {
+                            bail!("line {line}: crate outside of the labelled stacks");
+                        };
+                        if stack.len() != rows.len() - line {
+                            bail!("line {line}: crate in stack {} has nothing below it", i + 1);
+                        }
+                        stack.push(char::from(*letter));
+                    }
+                    _ if c.iter().all(|&b| b == b' ') => {}
+                    _ => bail!(
+                        "line {line}: '{}' is not a crate",
+                        String::from_utf8_lossy(c)
+                    ),
                 }
             }
         }
 
-        crates
+        Ok(crates)
     }
 
-    fn execute(&mut self, instruction: &Instruction) {
-        for _ in 0..instruction.amount {
-            let c = self.0[instruction.from - 1].pop().unwrap();
-            self.0[instruction.to - 1].push(c);
-        }
+    /// Moves the top `amount` crates in one go, keeping their order.
+    fn move_top(&mut self, from: usize, to: usize, amount: usize) {
+        let from = &mut self.0[from - 1];
+        let mut cs = from.drain(from.len() - amount..).collect();
+        self.0[to - 1].append(&mut cs);
+    }
+
+    /// Every instruction that can be carried out on these stacks.
+    fn possible_moves(&self) -> impl Iterator<Item = Instruction> + '_ {
+        let n = self.0.len();
+        (1..=n).flat_map(move |from| {
+            (1..=n).filter(move |&to| to != from).flat_map(move |to| {
+                (1..=self.0[from - 1].len()).map(move |amount| Instruction { amount, from, to })
+            })
+        })
     }
 
-    fn execute2(&mut self, instruction: &Instruction) {
-        let from = &mut self.0[instruction.from - 1];
-        let mut cs = from.drain(from.len() - instruction.amount..).collect();
-        self.0[instruction.to - 1].append(&mut cs);
+    /// All crates, sorted.
+    fn inventory(&self) -> Vec<char> {
+        let mut all = self.0.concat();
+        all.sort_unstable();
+        all
     }
 
+    /// Top crate of every stack, empty stacks are skipped.
     fn top_message(&self) -> String {
-        self.0.iter().map(|v| v.last().unwrap()).collect()
+        self.0.iter().filter_map(|v| v.last()).collect()
+    }
+}
+
+/// Draws the stacks the way the puzzle does, without a trailing newline.
+impl Display for Crates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let row: Vec<_> = self
+                .0
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("[{c}]"),
+                    None => "   ".into(),
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        let labels: Vec<_> = (1..=self.0.len()).map(|i| format!("{i:^3}")).collect();
+        write!(f, "{}", labels.join(" "))
+    }
+}
+
+/// A crane model, deciding how many crates it can carry at a time.
+pub trait Crane {
+    fn name(&self) -> String;
+
+    /// Carries out the instruction, returns how many lifts it took.
+    fn execute(&self, crates: &mut Crates, instruction: &Instruction) -> usize;
+}
+
+/// Moves crates one at a time.
+pub struct CrateMover9000;
+
+/// Moves any number of crates at once.
+pub struct CrateMover9001;
+
+/// Moves up to `capacity` crates at once.
+pub struct LimitedCrane {
+    capacity: usize,
+}
+
+impl LimitedCrane {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a crane must lift at least one crate");
+        Self { capacity }
+    }
+}
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".into()
+    }
+
+    fn execute(&self, crates: &mut Crates, instruction: &Instruction) -> usize {
+        LimitedCrane::new(1).execute(crates, instruction)
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".into()
+    }
+
+    fn execute(&self, crates: &mut Crates, instruction: &Instruction) -> usize {
+        if instruction.amount == 0 {
+            return 0;
+        }
+        crates.move_top(instruction.from, instruction.to, instruction.amount);
+        1
+    }
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("crane lifting up to {}", self.capacity)
+    }
+
+    fn execute(&self, crates: &mut Crates, instruction: &Instruction) -> usize {
+        let mut left = instruction.amount;
+        let mut lifts = 0;
+        while left > 0 {
+            let amount = left.min(self.capacity);
+            crates.move_top(instruction.from, instruction.to, amount);
+            left -= amount;
+            lifts += 1;
+        }
+        lifts
+    }
+}
+
+/// Instructions carried out one at a time by a crane, remembering the stacks after
+/// every step so that steps can be undone and redone.
+pub struct Execution<'a> {
+    crane: &'a dyn Crane,
+    instructions: Vec<Instruction>,
+    /// The stacks and the lifts so far after each of the steps run until now.
+    states: Vec<(Crates, usize)>,
+    /// How many steps are currently applied.
+    position: usize,
+}
+
+impl<'a> Execution<'a> {
+    pub fn new(crates: Crates, instructions: Vec<Instruction>, crane: &'a dyn Crane) -> Self {
+        Self {
+            crane,
+            instructions,
+            states: vec![(crates, 0)],
+            position: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Crates {
+        &self.states[self.position].0
+    }
+
+    pub fn lifts(&self) -> usize {
+        self.states[self.position].1
+    }
+
+    /// The stacks after the first `step` instructions, if they have been run.
+    pub fn after(&self, step: usize) -> Option<&Crates> {
+        self.states.get(step).map(|s| &s.0)
+    }
+
+    /// Runs the next instruction, returns `false` if there are none left.
+    pub fn step(&mut self) -> Result<bool> {
+        if self.redo() {
+            return Ok(true);
+        }
+        let Some(instruction) = self.instructions.get(self.position) else {
+            return Ok(false);
+        };
+        instruction
+            .validate(self.current())
+            .wrap_err_with(|| format!("instruction {}: {instruction}", self.position + 1))?;
+        let (mut crates, lifts) = self.states[self.position].clone();
+        let lifts = lifts + self.crane.execute(&mut crates, instruction);
+        self.states.push((crates, lifts));
+        self.position += 1;
+        Ok(true)
     }
+
+    pub fn run(&mut self) -> Result<()> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// Steps back one instruction, returns `false` if at the start.
+    pub fn undo(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.position -= 1;
+        true
+    }
+
+    /// Steps forward again after an undo, returns `false` if nothing was undone.
+    pub fn redo(&mut self) -> bool {
+        if self.position + 1 >= self.states.len() {
+            return false;
+        }
+        self.position += 1;
+        true
+    }
+}
+
+fn parse_input(data: &[String]) -> Result<(Crates, Vec<Instruction>)> {
+    let chunks: Vec<_> = paragraphs(data).collect();
+    let [diagram, instructions] = chunks[..] else {
+        bail!("expected a crate diagram and a list of instructions");
+    };
+    Ok((
+        Crates::parse(diagram.lines).wrap_err("crate diagram")?,
+        instructions.parse_lines(Instruction::parse)?,
+    ))
+}
+
+fn execution<'a>(data: &[String], crane: &'a dyn Crane) -> Result<Execution<'a>> {
+    let (crates, instructions) = parse_input(data)?;
+    Ok(Execution::new(crates, instructions, crane))
+}
+
+/// Top crates after running all instructions with the crane, and the lifts it took.
+pub fn operate(data: &[String], crane: &dyn Crane) -> Result<(String, usize)> {
+    let mut execution = execution(data, crane)?;
+    execution.run()?;
+    Ok((execution.current().top_message(), execution.lifts()))
 }
 
-fn parse_input(data: &[String]) -> (Crates, Vec<Instruction>) {
-    let chunks: Vec<_> = data.split(String::is_empty).collect();
-    (
-        Crates::parse(chunks[0]),
-        chunks[1].iter().map(|x| Instruction::parse(x)).collect(),
-    )
+/// The stacks drawn after the first `step` instructions.
+pub fn render_after(data: &[String], crane: &dyn Crane, step: usize) -> Result<String> {
+    let mut execution = execution(data, crane)?;
+    while execution.states.len() <= step && execution.step()? {}
+    let crates = execution.after(step).ok_or_else(|| {
+        eyre!(
+            "there are only {} instructions",
+            execution.instructions.len()
+        )
+    })?;
+    Ok(crates.to_string())
 }
 
-pub fn solution1(data: &[String]) -> String {
-    let (mut crates, instructions) = parse_input(data);
-    for i in instructions {
-        crates.execute(&i);
+/// The stacks drawn after running all instructions, then undoing the last `steps`.
+pub fn render_undone(data: &[String], crane: &dyn Crane, steps: usize) -> Result<String> {
+    let mut execution = execution(data, crane)?;
+    execution.run()?;
+    for _ in 0..steps {
+        if !execution.undo() {
+            bail!(
+                "there are only {} instructions to undo",
+                execution.instructions.len()
+            );
+        }
     }
-    crates.top_message()
+    Ok(execution.current().to_string())
 }
 
-pub fn solution2(data: &[String]) -> String {
-    let (mut crates, instructions) = parse_input(data);
-    for i in instructions {
-        crates.execute2(&i);
+/// Shortest list of instructions taking the crane from `start` to `target`, found
+/// with a breadth-first search over stack layouts.
+fn plan(start: &Crates, target: &Crates, crane: &dyn Crane) -> Result<Vec<Instruction>> {
+    if start.0.len() != target.0.len() {
+        bail!(
+            "start has {} stacks but target has {}",
+            start.0.len(),
+            target.0.len()
+        );
+    }
+    if start.inventory() != target.inventory() {
+        bail!("start and target do not have the same crates");
+    }
+
+    if start == target {
+        return Ok(vec![]);
+    }
+    // a fingerprint collision could hide a layout, with 64 bits that is not a worry
+    let fingerprint = |crates: &Crates| {
+        let mut hasher = DefaultHasher::new();
+        crates.hash(&mut hasher);
+        hasher.finish()
+    };
+    let mut seen = HashSet::from([fingerprint(start)]);
+    // for every layout seen so far, the one it was reached from and how
+    let mut links: Vec<Option<(usize, Instruction)>> = vec![None];
+    let path_to = |links: &[Option<(usize, Instruction)>], mut i: usize| {
+        let mut moves = vec![];
+        while let Some((prev, instruction)) = links[i] {
+            moves.push(instruction);
+            i = prev;
+        }
+        moves.reverse();
+        moves
+    };
+    let mut i = 0;
+    while i < links.len() {
+        let mut crates = start.clone();
+        for instruction in path_to(&links, i) {
+            crane.execute(&mut crates, &instruction);
+        }
+        for instruction in crates.possible_moves() {
+            let mut next = crates.clone();
+            crane.execute(&mut next, &instruction);
+            if next == *target {
+                let mut moves = path_to(&links, i);
+                moves.push(instruction);
+                return Ok(moves);
+            }
+            if seen.insert(fingerprint(&next)) {
+                links.push(Some((i, instruction)));
+            }
+        }
+        if links.len() > MAX_PLAN_STATES {
+            bail!("no plan found within {MAX_PLAN_STATES} stack layouts");
+        }
+        i += 1;
     }
-    crates.top_message()
+    bail!("{} cannot reach the target", crane.name())
+}
+
+/// Instructions that rearrange the input's stacks into the `target` diagram with the
+/// fewest steps, one per line in the puzzle's syntax.
+pub fn plan_moves(data: &[String], target: &[String], crane: &dyn Crane) -> Result<String> {
+    let diagram = |data| {
+        let lines = paragraphs(data).next().map_or(&[][..], |p| p.lines);
+        Crates::parse(lines)
+    };
+    let start = diagram(data).wrap_err("start diagram")?;
+    let target = diagram(target).wrap_err("target diagram")?;
+    let moves = plan(&start, &target, crane)?;
+    Ok(moves
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// What every crane model makes of the instructions.
+pub fn report(data: &[String], capacity: Option<usize>) -> Result<String> {
+    let mut cranes: Vec<Box<dyn Crane>> = vec![Box::new(CrateMover9000), Box::new(CrateMover9001)];
+    if let Some(capacity) = capacity {
+        cranes.push(Box::new(LimitedCrane::new(capacity)));
+    }
+    let mut out = String::new();
+    for crane in cranes {
+        let (message, lifts) = operate(data, crane.as_ref())?;
+        writeln!(out, "{}: {message} in {lifts} lifts", crane.name())?;
+    }
+    Ok(out)
+}
+
+pub fn solution1(data: &[String]) -> Result<String> {
+    Ok(operate(data, &CrateMover9000)?.0)
+}
+
+pub fn solution2(data: &[String]) -> Result<String> {
+    Ok(operate(data, &CrateMover9001)?.0)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        Crane, CrateMover9000, CrateMover9001, Crates, Execution, Instruction, LimitedCrane,
+    };
     use crate::{common::str2lines, day5};
 
     fn data() -> Vec<String> {
@@ -109,11 +463,118 @@ move 1 from 1 to 2"#,
 
     #[test]
     fn test_solution1() {
-        assert_eq!("CMZ", day5::solution1(&data()));
+        assert_eq!("CMZ", day5::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!("MCD", day5::solution2(&data()));
+        assert_eq!("MCD", day5::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_render() {
+        let (crates, _) = day5::parse_input(&data()).unwrap();
+        let diagram = crates.to_string();
+        assert_eq!(data()[..4].join("\n"), diagram);
+        assert_eq!(crates, Crates::parse(&str2lines(&diagram)).unwrap());
+        assert_eq!(
+            "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 ",
+            day5::render_after(&data(), &CrateMover9000, 2).unwrap()
+        );
+        assert_eq!(
+            day5::render_after(&data(), &CrateMover9000, 1).unwrap(),
+            day5::render_undone(&data(), &CrateMover9000, 3).unwrap()
+        );
+        assert_eq!(
+            "there are only 4 instructions",
+            day5::render_after(&data(), &CrateMover9000, 5)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "there are only 4 instructions to undo",
+            day5::render_undone(&data(), &CrateMover9000, 5)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut execution = day5::execution(&data(), &CrateMover9000).unwrap();
+        assert!(!execution.undo());
+        execution.run().unwrap();
+        assert_eq!("CMZ", execution.current().top_message());
+        assert!(execution.undo());
+        assert!(execution.undo());
+        // stack 1 is empty by now
+        assert_eq!("CZ", execution.current().top_message());
+        assert_eq!("DCP", execution.after(1).unwrap().top_message());
+        assert_eq!(4, execution.lifts());
+        assert!(execution.redo());
+        assert!(execution.step().unwrap());
+        assert!(!execution.redo());
+        assert!(!execution.step().unwrap());
+        assert_eq!(7, execution.lifts());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bad = data();
+        bad[6] = "move 4 from 1 to 3".into();
+        let err = day5::solution1(&bad).unwrap_err();
+        assert_eq!("instruction 2: move 4 from 1 to 3", err.to_string());
+        assert_eq!("stack 1 has only 3 crates", err.root_cause().to_string());
+
+        bad[6] = "move 1 from 1 to 4".into();
+        let err = day5::solution1(&bad).unwrap_err();
+        assert_eq!("there is no stack 4", err.root_cause().to_string());
+
+        let mut floating = data();
+        floating[0] = "    [D] [X]".into();
+        let err = day5::solution1(&floating).unwrap_err();
+        assert_eq!(
+            "line 1: crate in stack 3 has nothing below it",
+            err.root_cause().to_string()
+        );
+
+        let empty = Crates(vec![vec!['A'], vec![], vec!['B']]);
+        assert_eq!("AB", empty.top_message());
+    }
+
+    #[test]
+    fn test_plan() {
+        let target = str2lines("        [D]\n    [C] [N]\n[Z] [M] [P]\n 1   2   3 ");
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let plan = day5::plan_moves(&data(), &target, crane).unwrap();
+            let instructions: Vec<_> = plan
+                .lines()
+                .map(|l| Instruction::parse(l).unwrap())
+                .collect();
+            let (start, _) = day5::parse_input(&data()).unwrap();
+            let mut execution = Execution::new(start, instructions, crane);
+            execution.run().unwrap();
+            assert_eq!(target.join("\n"), execution.current().to_string());
+        }
+        assert_eq!(
+            "move 1 from 1 to 3\nmove 1 from 2 to 3",
+            day5::plan_moves(&data(), &target, &CrateMover9001).unwrap()
+        );
+        let missing = str2lines("[Z] [N] [D]\n 1   2   3 ");
+        let err = day5::plan_moves(&data(), &missing, &CrateMover9000).unwrap_err();
+        assert_eq!(
+            "start and target do not have the same crates",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_cranes() {
+        let operate = |crane: &dyn Crane| day5::operate(&data(), crane).unwrap();
+        assert_eq!(("CMZ".into(), 7), operate(&CrateMover9000));
+        assert_eq!(("MCD".into(), 4), operate(&CrateMover9001));
+        // the three crates go over as two, then one
+        assert_eq!(("MCZ".into(), 5), operate(&LimitedCrane::new(2)));
+        assert_eq!(("MCD".into(), 4), operate(&LimitedCrane::new(3)));
     }
 }
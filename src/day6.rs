@@ -1,46 +1,310 @@
-fn find_n_unique(n: usize, s: &str) -> Option<usize> {
-    let mut sofar: String = String::new();
+use std::fmt::Write;
+use std::io::{self, ErrorKind, Read};
 
-    for (i, c) in s.chars().enumerate() {
-        if let Some(x) = sofar.find(c) {
-            sofar.drain(..=x);
+use color_eyre::eyre::{eyre, Result};
+
+/// Follows a stream byte by byte, keeping track of the run of distinct bytes
+/// that ends at the latest one. Any byte value counts as a character.
+struct Detector {
+    /// For every byte value, the position just after where it was last seen, 0 if never.
+    last_seen: [usize; 256],
+    /// Where the current run of distinct bytes starts.
+    run_start: usize,
+    /// How many bytes have been seen.
+    position: usize,
+}
+
+impl Detector {
+    fn new() -> Self {
+        Self {
+            last_seen: [0; 256],
+            run_start: 0,
+            position: 0,
+        }
+    }
+
+    /// Takes the next byte, returns the length of the distinct run ending with it.
+    fn push(&mut self, b: u8) -> usize {
+        let seen = &mut self.last_seen[usize::from(b)];
+        self.run_start = self.run_start.max(*seen);
+        self.position += 1;
+        *seen = self.position;
+        self.position - self.run_start
+    }
+}
+
+/// Position just after the first `n` distinct bytes in a row, read in chunks so
+/// the stream can be of any size.
+pub fn find_marker(mut reader: impl Read, n: usize) -> io::Result<Option<usize>> {
+    if n == 0 {
+        return Ok(Some(0));
+    }
+    let mut detector = Detector::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => return Ok(None),
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &b in &buf[..len] {
+            if detector.push(b) == n {
+                return Ok(Some(detector.position));
+            }
+        }
+    }
+}
+
+/// Like [`find_marker`], but for text: line terminators are not characters, so
+/// a trailing newline cannot complete a marker.
+pub fn find_text_marker(reader: impl Read, n: usize) -> io::Result<Option<usize>> {
+    find_marker(WithoutLineEnds(reader), n)
+}
+
+/// A reader that drops every `\r` and `\n`.
+struct WithoutLineEnds<R>(R);
+
+impl<R: Read> Read for WithoutLineEnds<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let len = self.0.read(buf)?;
+            let mut kept = 0;
+            for i in 0..len {
+                if !matches!(buf[i], b'\r' | b'\n') {
+                    buf[kept] = buf[i];
+                    kept += 1;
+                }
+            }
+            // a read of nothing but line ends is not the end of the stream
+            if kept > 0 || len == 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+/// Queries over a whole captured signal.
+pub struct Signal<'a>(&'a [u8]);
+
+impl<'a> Signal<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    /// Every position just after `n` distinct bytes in a row, overlapping ones included.
+    pub fn markers(&self, n: usize) -> Vec<usize> {
+        let mut detector = Detector::new();
+        let mut result: Vec<usize> = if n == 0 { vec![0] } else { vec![] };
+        for &b in self.0 {
+            if detector.push(b) >= n {
+                result.push(detector.position);
+            }
+        }
+        result
+    }
+
+    /// Start and length of the first of the longest runs of distinct bytes.
+    pub fn longest_run(&self) -> (usize, usize) {
+        let mut detector = Detector::new();
+        let mut longest = (0, 0);
+        for &b in self.0 {
+            let len = detector.push(b);
+            if len > longest.1 {
+                longest = (detector.run_start, len);
+            }
+        }
+        longest
+    }
+
+    /// How many windows of `n` bytes contain 0, 1, ... `n` distinct bytes.
+    pub fn distinct_histogram(&self, n: usize) -> Vec<usize> {
+        let mut histogram = vec![0; n + 1];
+        if n == 0 || n > self.0.len() {
+            return histogram;
+        }
+        let mut counts = [0_usize; 256];
+        let mut distinct = 0;
+        for (i, &b) in self.0.iter().enumerate() {
+            counts[usize::from(b)] += 1;
+            if counts[usize::from(b)] == 1 {
+                distinct += 1;
+            }
+            if i >= n {
+                let old = usize::from(self.0[i - n]);
+                counts[old] -= 1;
+                if counts[old] == 0 {
+                    distinct -= 1;
+                }
+            }
+            if i + 1 >= n {
+                histogram[distinct] += 1;
+            }
+        }
+        histogram
+    }
+}
+
+/// Positions such as `4, 7-9`, consecutive ones joined.
+fn show_positions(positions: &[usize]) -> String {
+    if positions.is_empty() {
+        return "none".into();
+    }
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for &p in positions {
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == p => run.1 = p,
+            _ => runs.push((p, p)),
         }
-        sofar.push(c);
-        if sofar.len() == n {
-            return Some(i + 1);
+    }
+    runs.iter()
+        .map(|&(a, b)| {
+            if a == b {
+                a.to_string()
+            } else {
+                format!("{a}-{b}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// All markers, the longest distinct run and the distinct bytes in windows of `n`.
+pub fn report(data: &[String], n: usize) -> Result<String> {
+    let line = data.first().ok_or_else(|| eyre!("the input is empty"))?;
+    let signal = Signal::new(line.as_bytes());
+    let mut out = String::new();
+    for (name, size) in [("start-of-packet", 4), ("start-of-message", 14)] {
+        let markers = signal.markers(size);
+        writeln!(
+            out,
+            "{name} markers ({}): {}",
+            markers.len(),
+            show_positions(&markers)
+        )?;
+    }
+    let (start, len) = signal.longest_run();
+    writeln!(out, "longest distinct run: {len} from {start}")?;
+    writeln!(out, "distinct characters per window of {n}:")?;
+    for (distinct, count) in signal.distinct_histogram(n).iter().enumerate() {
+        if *count > 0 {
+            writeln!(out, "{distinct:>4} {count}")?;
         }
     }
+    Ok(out)
+}
 
-    None
+/// Position just after the first `n` distinct characters in a row of streamed text.
+pub fn text_marker(reader: impl Read, n: usize) -> Result<usize> {
+    find_text_marker(reader, n)?.ok_or_else(|| eyre!("no {n} distinct characters in a row"))
 }
 
-pub fn solution1(data: &[String]) -> usize {
-    find_n_unique(4, &data[0]).unwrap()
+pub fn solution1(reader: impl Read) -> Result<usize> {
+    text_marker(reader, 4)
 }
 
-pub fn solution2(data: &[String]) -> usize {
-    find_n_unique(14, &data[0]).unwrap()
+pub fn solution2(reader: impl Read) -> Result<usize> {
+    text_marker(reader, 14)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
+
+    use super::Signal;
     use crate::day6;
 
     #[test]
     fn test_solution1() {
         assert_eq!(
             7,
-            day6::solution1(&["mjqjpqmgbljsphdztnvjfqwrcgsmlb".into()])
+            day6::solution1("mjqjpqmgbljsphdztnvjfqwrcgsmlb".as_bytes()).unwrap()
+        );
+        assert_eq!(
+            5,
+            day6::solution1("bvwbjplbgvbhsrlpgdmjqwftvncz".as_bytes()).unwrap()
+        );
+        assert_eq!(
+            6,
+            day6::solution1("nppdvjthqldpwncqszvftbrmjlhg".as_bytes()).unwrap()
         );
-        assert_eq!(5, day6::solution1(&["bvwbjplbgvbhsrlpgdmjqwftvncz".into()]));
-        assert_eq!(6, day6::solution1(&["nppdvjthqldpwncqszvftbrmjlhg".into()]));
         assert_eq!(
             10,
-            day6::solution1(&["nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg".into()])
+            day6::solution1("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg".as_bytes()).unwrap()
         );
         assert_eq!(
             11,
-            day6::solution1(&["zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw".into()])
+            day6::solution1("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw".as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_solution2() {
+        assert_eq!(
+            19,
+            day6::solution2("mjqjpqmgbljsphdztnvjfqwrcgsmlb".as_bytes()).unwrap()
+        );
+        assert_eq!(
+            26,
+            day6::solution2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw".as_bytes()).unwrap()
+        );
+        let err = day6::solution2("abcabc\n".as_bytes()).unwrap_err();
+        assert_eq!("no 14 distinct characters in a row", err.to_string());
+        assert!(day6::solution1(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_signal() {
+        let signal = Signal::new(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb");
+        assert_eq!(Some(&7), signal.markers(4).first());
+        assert_eq!(vec![19, 25, 26, 27, 28, 29, 30], signal.markers(14));
+        assert_eq!((12, 18), signal.longest_run());
+        assert_eq!(vec![0, 0, 0, 3, 24], signal.distinct_histogram(4));
+        assert_eq!(vec![0, 0, 2, 0], Signal::new(b"abab").distinct_histogram(3));
+        assert_eq!(vec![0, 1, 1], Signal::new(b"aab").distinct_histogram(2));
+        assert_eq!(vec![0; 6], Signal::new(b"aab").distinct_histogram(5));
+    }
+
+    #[test]
+    fn test_report() {
+        let report = day6::report(&["abcabcd".into()], 3).unwrap();
+        assert_eq!(
+            "start-of-packet markers (1): 7
+start-of-message markers (0): none
+longest distinct run: 4 from 3
+distinct characters per window of 3:
+   3 5
+",
+            report
+        );
+        let err = day6::report(&[], 3).unwrap_err();
+        assert_eq!("the input is empty", err.to_string());
+    }
+
+    #[test]
+    fn test_find_marker() {
+        // any byte is a character, and the marker may straddle reads
+        let stream = (&[0_u8, 0, 255, 7, 0][..]).chain(&[1_u8, 2][..]);
+        assert_eq!(Some(6), day6::find_marker(stream, 4).unwrap());
+        assert_eq!(Some(0), day6::find_marker(&b""[..], 0).unwrap());
+        assert_eq!(None, day6::find_marker(&b"aaaa"[..], 2).unwrap());
+
+        let all: Vec<u8> = (0..=255).chain(0..=255).collect();
+        assert_eq!(Some(256), day6::find_marker(&all[..], 256).unwrap());
+        assert_eq!(None, day6::find_marker(&all[..], 257).unwrap());
+    }
+
+    #[test]
+    fn test_find_text_marker() {
+        assert_eq!(Some(4), day6::find_marker(&b"abc\n"[..], 4).unwrap());
+        assert_eq!(None, day6::find_text_marker(&b"abc\n"[..], 4).unwrap());
+        assert_eq!(None, day6::find_text_marker(&b"\n\r\n"[..], 1).unwrap());
+        assert_eq!(
+            Some(7),
+            day6::find_text_marker(&b"mjqjpqmgbljsphdztnvjfqwrcgsmlb\n"[..], 4).unwrap()
         );
+        // a marker can span lines, only the characters on them are counted
+        let lines = (&b"ab\r\n"[..]).chain(&b"\ncd\n"[..]);
+        assert_eq!(Some(4), day6::find_text_marker(lines, 4).unwrap());
     }
 }
@@ -1,123 +1,818 @@
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    rc::{Rc, Weak},
-};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs::{self, OpenOptions};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
-use lazy_static::lazy_static;
-use regex::Regex;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
 
-type DirLink = Rc<RefCell<Dir>>;
-type DirWeak = Weak<RefCell<Dir>>;
+/// Index of a directory in its `FileSystem`.
+type DirId = usize;
 
-// I know this solution is silly, but my main goal was to struggle with
-// Rust's borrow machinery, not solving the bloody problem.
-// Not sure I'm using Rc<> here as God intended.
+/// How many partial plans the deletion planner keeps for a directory before giving up.
+const MAX_PLAN_STATES: usize = 1_000_000;
+
+/// How many totals the deletion planner tracks. Beyond that, sizes are compared
+/// in units of 2, 4, 8, ... bytes.
+const MAX_PLAN_SUMS: usize = 1 << 22;
 
 #[derive(Debug)]
 struct Dir {
-    #[allow(dead_code)]
     name: String,
-    parent: Option<DirWeak>,
-    files: HashMap<String, usize>,
-    dirs: HashMap<String, DirLink>,
+    parent: Option<DirId>,
+    files: BTreeMap<String, usize>,
+    dirs: BTreeMap<String, DirId>,
+    /// Size of all files in this directory and below, kept up to date as files are added.
+    size: usize,
+    /// Whether an `ls` of it has been seen, so later ones must list the same entries.
+    listed: bool,
+}
+
+/// Directory tree stored as an arena, the root first.
+#[derive(Debug)]
+pub struct FileSystem {
+    dirs: Vec<Dir>,
 }
 
-impl Dir {
-    pub fn new(name: &str, parent: Option<DirWeak>) -> Self {
-        Dir {
+impl FileSystem {
+    const ROOT: DirId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            dirs: vec![Dir {
+                name: "/".into(),
+                parent: None,
+                files: BTreeMap::new(),
+                dirs: BTreeMap::new(),
+                size: 0,
+                listed: false,
+            }],
+        }
+    }
+
+    fn dir(&self, id: DirId) -> &Dir {
+        &self.dirs[id]
+    }
+
+    pub fn parent(&self, id: DirId) -> Option<DirId> {
+        self.dirs[id].parent
+    }
+
+    pub fn child(&self, id: DirId, name: &str) -> Option<DirId> {
+        self.dirs[id].dirs.get(name).copied()
+    }
+
+    /// Subdirectories in name order.
+    pub fn children(&self, id: DirId) -> impl Iterator<Item = DirId> + '_ {
+        self.dirs[id].dirs.values().copied()
+    }
+
+    /// Total size of the directory, including everything below it.
+    pub fn size(&self, id: DirId) -> usize {
+        self.dirs[id].size
+    }
+
+    /// Path of an entry in a directory.
+    fn entry_path(&self, dir: DirId, name: &str) -> String {
+        match self.parent(dir) {
+            None => format!("/{name}"),
+            Some(_) => format!("{}/{name}", self.path(dir)),
+        }
+    }
+
+    /// Returns the subdirectory, creating it if needed.
+    fn add_dir(&mut self, parent: DirId, name: &str) -> Result<DirId> {
+        check_name(name)?;
+        if self.dirs[parent].files.contains_key(name) {
+            bail!("{} is a file", self.entry_path(parent, name));
+        }
+        if let Some(id) = self.child(parent, name) {
+            return Ok(id);
+        }
+        let id = self.dirs.len();
+        self.dirs.push(Dir {
             name: name.into(),
-            parent,
-            files: HashMap::new(),
-            dirs: HashMap::new(),
+            parent: Some(parent),
+            files: BTreeMap::new(),
+            dirs: BTreeMap::new(),
+            size: 0,
+            listed: false,
+        });
+        self.dirs[parent].dirs.insert(name.into(), id);
+        Ok(id)
+    }
+
+    /// Adds a file, which may already be known but only with the same size.
+    fn add_file(&mut self, dir: DirId, name: &str, size: usize) -> Result<()> {
+        check_name(name)?;
+        if self.dirs[dir].dirs.contains_key(name) {
+            bail!("{} is a directory", self.entry_path(dir, name));
+        }
+        match self.dirs[dir].files.get(name) {
+            Some(&old) if old != size => bail!(
+                "{} was {old} bytes and is now {size}",
+                self.entry_path(dir, name)
+            ),
+            Some(_) => return Ok(()),
+            None => {}
+        }
+        self.dirs[dir].files.insert(name.into(), size);
+        let mut current = Some(dir);
+        while let Some(id) = current {
+            self.dirs[id].size += size;
+            current = self.dirs[id].parent;
         }
+        Ok(())
     }
 
-    pub fn new_link(name: &str, parent: Option<DirWeak>) -> DirLink {
-        Rc::new(RefCell::new(Dir::new(name, parent)))
+    /// Checks a finished `ls` against what an earlier one listed.
+    fn end_listing(&mut self, dir: DirId, seen: &BTreeSet<&str>) -> Result<()> {
+        let d = &self.dirs[dir];
+        if d.listed {
+            let missing: Vec<_> = (d.files.keys().chain(d.dirs.keys()))
+                .map(String::as_str)
+                .filter(|name| !seen.contains(name))
+                .collect();
+            if !missing.is_empty() {
+                bail!(
+                    "listing of {} is missing {}",
+                    self.path(dir),
+                    missing.join(", ")
+                );
+            }
+        }
+        self.dirs[dir].listed = true;
+        Ok(())
     }
-}
 
-fn parse(input: &[String]) -> DirLink {
-    lazy_static! {
-        static ref RE_CD: Regex = Regex::new(r"^\$ cd (\S+)$").unwrap();
-        static ref RE_FILE: Regex = Regex::new(r"^([0-9]+) (\S+)$").unwrap();
+    /// Absolute path such as `/a/e`.
+    pub fn path(&self, id: DirId) -> String {
+        let mut names = vec![];
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            names.push(self.dir(current).name.as_str());
+            current = parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Finds a directory by its absolute path.
+    pub fn lookup(&self, path: &str) -> Option<DirId> {
+        let rest = path.strip_prefix('/')?;
+        rest.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(Self::ROOT, |id, name| self.child(id, name))
     }
 
-    let root = Dir::new_link("/", None);
-    let mut current = Rc::clone(&root);
+    /// Every directory, each one before those inside it.
+    pub fn pre_order(&self) -> impl Iterator<Item = DirId> + '_ {
+        let mut stack = vec![Self::ROOT];
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.dir(id).dirs.values().rev());
+            Some(id)
+        })
+    }
 
-    for line in input {
-        if let Some(caps) = RE_FILE.captures(line) {
-            current
-                .borrow_mut()
-                .files
-                .insert(caps[2].into(), caps[1].parse().unwrap());
-        } else if let Some(caps) = RE_CD.captures(line) {
-            let dirname = &caps[1];
-            if dirname == "/" {
-                current = Rc::clone(&root);
-            } else if dirname == ".." {
-                let x = current.borrow().parent.as_ref().unwrap().upgrade().unwrap();
-                current = x;
+    /// Every directory, each one after those inside it.
+    pub fn post_order(&self) -> impl Iterator<Item = DirId> {
+        // pre-order with the children taken in reverse, backwards
+        let mut order = vec![];
+        let mut stack = vec![Self::ROOT];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.children(id));
+        }
+        order.into_iter().rev()
+    }
+
+    /// Directories whose size is in range, or which have one below them.
+    fn kept(&self, sizes: &RangeInclusive<usize>) -> Vec<bool> {
+        let mut kept = vec![false; self.dirs.len()];
+        for id in self.post_order() {
+            kept[id] = sizes.contains(&self.size(id)) || self.children(id).any(|c| kept[c]);
+        }
+        kept
+    }
+
+    /// The tree as drawn in the puzzle, entries in name order. Only directories
+    /// with a size in range and their files are shown, plus the directories
+    /// leading to them.
+    pub fn tree(&self, sizes: &RangeInclusive<usize>) -> Result<String> {
+        let kept = self.kept(sizes);
+        let mut out = String::new();
+        if kept[Self::ROOT] {
+            self.write_tree(&mut out, Self::ROOT, 0, &kept, sizes)?;
+        }
+        Ok(out)
+    }
+
+    fn write_tree(
+        &self,
+        out: &mut String,
+        id: DirId,
+        depth: usize,
+        kept: &[bool],
+        sizes: &RangeInclusive<usize>,
+    ) -> Result<()> {
+        let dir = self.dir(id);
+        let indent = "  ".repeat(depth);
+        writeln!(out, "{indent}- {} (dir, size={})", dir.name, dir.size)?;
+
+        let mut entries: Vec<(&str, Result<DirId, usize>)> = dir
+            .dirs
+            .iter()
+            .filter(|(_, &child)| kept[child])
+            .map(|(name, &child)| (name.as_str(), Ok(child)))
+            .collect();
+        if sizes.contains(&dir.size) {
+            entries.extend(
+                dir.files
+                    .iter()
+                    .map(|(name, &size)| (name.as_str(), Err(size))),
+            );
+        }
+        entries.sort_unstable();
+        for (name, entry) in entries {
+            match entry {
+                Ok(child) => self.write_tree(out, child, depth + 1, kept, sizes)?,
+                Err(size) => writeln!(out, "{indent}  - {name} (file, size={size})")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Directories in range with their sizes like `du -h`, largest first.
+    pub fn du(&self, sizes: &RangeInclusive<usize>) -> Result<String> {
+        let mut dirs: Vec<_> = self
+            .pre_order()
+            .filter(|&id| sizes.contains(&self.size(id)))
+            .map(|id| (self.size(id), self.path(id)))
+            .collect();
+        dirs.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let mut out = String::new();
+        for (size, path) in dirs {
+            writeln!(out, "{:<6}{path}", human(size))?;
+        }
+        Ok(out)
+    }
+
+    /// A transcript that lists every directory before entering it, which `parse`
+    /// turns back into the same filesystem.
+    pub fn transcript(&self) -> Vec<String> {
+        let mut lines = vec!["$ cd /".to_string()];
+        self.write_transcript(&mut lines, Self::ROOT);
+        lines
+    }
+
+    fn write_transcript(&self, lines: &mut Vec<String>, id: DirId) {
+        let dir = self.dir(id);
+        lines.push("$ ls".into());
+        let mut entries: Vec<_> = (dir.dirs.keys().map(|name| (name, format!("dir {name}"))))
+            .chain(
+                dir.files
+                    .iter()
+                    .map(|(name, size)| (name, format!("{size} {name}"))),
+            )
+            .collect();
+        entries.sort_unstable();
+        lines.extend(entries.into_iter().map(|(_, line)| line));
+        for (name, &child) in &dir.dirs {
+            lines.push(format!("$ cd {name}"));
+            self.write_transcript(lines, child);
+            lines.push("$ cd ..".into());
+        }
+    }
+
+    /// Creates the tree under `root`, with sparse files of the recorded sizes.
+    /// Nothing that already exists is overwritten, and nothing is created outside `root`.
+    pub fn materialize(&self, root: &Path) -> Result<()> {
+        let inside = |path: PathBuf| {
+            if path.starts_with(root) {
+                Ok(path)
             } else {
-                let new_dir = Dir::new_link(dirname, Some(Rc::downgrade(&current)));
-                current
-                    .borrow_mut()
-                    .dirs
-                    .insert(dirname.into(), Rc::clone(&new_dir));
-                current = Rc::clone(&new_dir);
+                Err(eyre!("{} is outside {}", path.display(), root.display()))
+            }
+        };
+        fs::create_dir_all(root).wrap_err_with(|| format!("creating {}", root.display()))?;
+        for id in self.pre_order() {
+            let dir = inside(root.join(&self.path(id)[1..]))?;
+            if id != Self::ROOT {
+                fs::create_dir(&dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
             }
+            for (name, &size) in &self.dir(id).files {
+                let path = inside(dir.join(name))?;
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .and_then(|file| file.set_len(u64::try_from(size).unwrap()))
+                    .wrap_err_with(|| format!("creating {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the directories and regular files under `root`, skipping symlinks.
+    pub fn scan(root: &Path) -> Result<Self> {
+        let mut fs = Self::new();
+        let mut stack = vec![(root.to_path_buf(), Self::ROOT)];
+        while let Some((path, id)) = stack.pop() {
+            let entries =
+                fs::read_dir(&path).wrap_err_with(|| format!("reading {}", path.display()))?;
+            for entry in entries {
+                let entry = entry.wrap_err_with(|| format!("reading {}", path.display()))?;
+                let name = entry.file_name();
+                let name = match name.to_str() {
+                    Some(name) if !name.contains(char::is_whitespace) => name,
+                    _ => bail!(
+                        "{} cannot be written in a transcript",
+                        entry.path().display()
+                    ),
+                };
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    let child = fs.add_dir(id, name)?;
+                    stack.push((entry.path(), child));
+                } else if file_type.is_file() {
+                    let size = entry.metadata()?.len();
+                    fs.add_file(id, name, usize::try_from(size)?)?;
+                }
+            }
+        }
+        Ok(fs)
+    }
+
+    /// How much has to be deleted to have `needed` bytes free on a disk of `disk` bytes.
+    fn shortfall(&self, disk: usize, needed: usize) -> Result<usize> {
+        let used = self.size(Self::ROOT);
+        if needed > disk {
+            bail!("cannot have {needed} bytes free on a disk of {disk}");
+        }
+        if used > disk {
+            bail!("{used} bytes do not fit on a disk of {disk}");
+        }
+        Ok((used + needed).saturating_sub(disk))
+    }
+
+    /// Directories other than the root in pre-order, their sizes, and for each
+    /// the position just after everything inside it.
+    fn chain(&self) -> (Vec<DirId>, Vec<usize>, Vec<usize>) {
+        let order: Vec<_> = self.pre_order().skip(1).collect();
+        let mut inside = vec![1; self.dirs.len()];
+        for id in self.post_order() {
+            inside[id] += self.children(id).map(|c| inside[c]).sum::<usize>();
         }
+        let sizes = order.iter().map(|&id| self.size(id)).collect();
+        let ends = (order.iter().enumerate())
+            .map(|(i, &id)| i + inside[id])
+            .collect();
+        (order, sizes, ends)
     }
 
-    root
+    /// Directories, none inside another nor the root, that free at least `amount`
+    /// bytes between them with as little as possible of what `goal` asks for.
+    ///
+    /// Any such set is a walk along the directories in pre-order that either skips
+    /// one or deletes it and jumps past everything inside it.
+    pub fn plan_deletions(&self, amount: usize, goal: Goal) -> Result<(usize, Vec<DirId>)> {
+        if amount == 0 {
+            return Ok((0, vec![]));
+        }
+        let (order, sizes, ends) = self.chain();
+        let deletable: usize = self.children(Self::ROOT).map(|id| self.size(id)).sum();
+        if deletable < amount {
+            bail!("deleting every directory frees only {deletable} of the {amount} bytes needed");
+        }
+        let total = |picked: &[usize]| picked.iter().map(|&i| sizes[i]).sum::<usize>();
+        // a plan known to be good enough, in case rounding hides every better one
+        let (fallback, max_count) = match goal {
+            Goal::Excess => (enough(&sizes, &ends, amount), None),
+            Goal::Deletions => {
+                let most = fewest_deletions(&sizes, &ends, amount)?;
+                let count = most.len();
+                (most, Some(count))
+            }
+        };
+        let picked = least_excess(&sizes, &ends, amount, max_count, total(&fallback))?
+            .filter(|picked| total(picked) <= total(&fallback))
+            .unwrap_or(fallback);
+        let freed = total(&picked);
+        let mut dirs: Vec<_> = picked.into_iter().map(|i| order[i]).collect();
+        dirs.sort_unstable_by_key(|&id| self.path(id));
+        Ok((freed, dirs))
+    }
 }
 
-fn total_size(dir: &DirLink) -> usize {
-    dir.borrow().files.values().sum::<usize>()
-        + dir
-            .borrow()
-            .dirs
-            .values()
-            .map(|x| total_size(&Rc::clone(x)))
-            .sum::<usize>()
+/// What a deletion plan should have as little of as possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Space freed beyond what is needed.
+    Excess,
+    /// Directories deleted. Of the plans with the fewest, the one with the least excess is taken.
+    Deletions,
 }
 
-fn all_dirs(dir: &DirLink) -> Vec<DirLink> {
-    let mut result = vec![Rc::clone(dir)];
-    for d in dir.borrow().dirs.values() {
-        result.append(&mut all_dirs(&Rc::clone(d)));
+/// Positions along the chain of directories where deleting each one leads.
+fn arrivals(ends: &[usize]) -> Vec<Vec<usize>> {
+    let mut arriving = vec![vec![]; ends.len() + 1];
+    for (i, &end) in ends.iter().enumerate() {
+        arriving[end].push(i);
     }
-    result
+    arriving
 }
 
-pub fn solution1(data: &[String]) -> usize {
-    let root = parse(data);
-    all_dirs(&root)
+/// ORs `src` moved up by `shift` bits into `dst`.
+fn or_shifted(dst: &mut [u64], src: &[u64], shift: usize) {
+    let (words, bits) = (shift / 64, shift % 64);
+    for k in words..dst.len() {
+        let mut word = src[k - words] << bits;
+        if bits > 0 && k > words {
+            word |= src[k - words - 1] >> (64 - bits);
+        }
+        dst[k] |= word;
+    }
+}
+
+/// The smallest directory freeing `amount` on its own, or else all of them.
+fn enough(sizes: &[usize], ends: &[usize], amount: usize) -> Vec<usize> {
+    let n = sizes.len();
+    match (0..n)
+        .filter(|&i| sizes[i] >= amount)
+        .min_by_key(|&i| sizes[i])
+    {
+        Some(i) => vec![i],
+        None => std::iter::successors((n > 0).then_some(0), |&i| Some(ends[i]).filter(|&e| e < n))
+            .collect(),
+    }
+}
+
+/// The walk freeing the least that is still at least `amount`, looking at totals up
+/// to `limit` and, if given, at most `max_count` deletions.
+///
+/// Totals reachable at a position stay reachable further on, so for every total
+/// (and number of deletions) it is enough to remember the first position reaching
+/// it; the walk is then retraced from the end. Sizes are rounded down to a unit
+/// that keeps the totals within `MAX_PLAN_SUMS`, so every walk found frees enough,
+/// but past whole bytes it may not be the very best one, or none may be found.
+fn least_excess(
+    sizes: &[usize],
+    ends: &[usize],
+    amount: usize,
+    max_count: Option<usize>,
+    limit: usize,
+) -> Result<Option<Vec<usize>>> {
+    let n = sizes.len();
+    let layers = max_count.map_or(1, |c| c + 1);
+    if 2 * layers > MAX_PLAN_SUMS {
+        bail!("plans deleting {layers} directories are too many to search");
+    }
+    let mut unit = 1;
+    while layers * (limit / unit + 1) > MAX_PLAN_SUMS {
+        unit *= 2;
+    }
+    let scaled: Vec<usize> = sizes.iter().map(|&x| x / unit).collect();
+    let (goal, len) = (amount.div_ceil(unit), limit / unit + 1);
+    let words = len.div_ceil(64);
+    let last_mask = u64::MAX >> (words * 64 - len);
+    // deleting moves a walk to the next layer when counting, else it stays in the only one
+    let next_layer = |layer: usize| if max_count.is_some() { layer + 1 } else { 0 };
+
+    let mut reached = vec![0_u64; layers * words];
+    reached[0] = 1;
+    let mut first = vec![u32::MAX; layers * len];
+    first[0] = 0;
+    let mut pending: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+    for i in 0..=n {
+        if let Some(mut arrived) = pending.remove(&i) {
+            for layer in 0..layers {
+                arrived[layer * words + words - 1] &= last_mask;
+                for k in 0..words {
+                    let word = arrived[layer * words + k];
+                    let mut new = word & !reached[layer * words + k];
+                    while new != 0 {
+                        let total = k * 64 + new.trailing_zeros() as usize;
+                        first[layer * len + total] = u32::try_from(i)?;
+                        new &= new - 1;
+                    }
+                    reached[layer * words + k] |= word;
+                }
+            }
+        }
+        if i < n && scaled[i] < len {
+            let target = pending
+                .entry(ends[i])
+                .or_insert_with(|| vec![0; layers * words]);
+            for layer in (0..layers).filter(|&l| next_layer(l) < layers) {
+                let to = next_layer(layer) * words;
+                let from = layer * words;
+                or_shifted(
+                    &mut target[to..to + words],
+                    &reached[from..from + words],
+                    scaled[i],
+                );
+            }
+        }
+    }
+
+    let reached_at = |layer: usize, total: usize| first[layer * len + total] as usize;
+    let Some((mut layer, mut total)) = (goal..len).find_map(|total| {
+        (0..layers)
+            .find(|&l| first[l * len + total] != u32::MAX)
+            .map(|l| (l, total))
+    }) else {
+        return Ok(None);
+    };
+    let arriving = arrivals(ends);
+    let mut picked = vec![];
+    let mut position = n;
+    while total > 0 || layer > 0 {
+        if reached_at(layer, total) < position {
+            position -= 1;
+            continue;
+        }
+        let before = if max_count.is_some() { layer - 1 } else { 0 };
+        let i = arriving[position]
+            .iter()
+            .copied()
+            .find(|&i| scaled[i] <= total && reached_at(before, total - scaled[i]) <= i)
+            .unwrap();
+        picked.push(i);
+        (layer, total) = (before, total - scaled[i]);
+        position = i;
+    }
+    Ok(Some(picked))
+}
+
+/// The walk deleting the fewest directories to free `amount`, freeing as much as
+/// that many can.
+fn fewest_deletions(sizes: &[usize], ends: &[usize], amount: usize) -> Result<Vec<usize>> {
+    let n = sizes.len();
+    let most = ends
         .iter()
-        .map(|x| total_size(&Rc::clone(x)))
+        .enumerate()
+        .filter(|&(i, &e)| e == i + 1)
+        .count();
+    if (n + 1) * (most + 1) > MAX_PLAN_STATES {
+        bail!("no plan found within {MAX_PLAN_STATES} partial plans");
+    }
+    // best[position][count]: most freed, and the position it came from
+    let mut best: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; most + 1]; n + 1];
+    best[0][0] = Some((0, 0));
+    for i in 0..n {
+        for count in 0..=most {
+            let Some((freed, _)) = best[i][count] else {
+                continue;
+            };
+            let mut offer = |position: usize, count: usize, freed: usize| {
+                if best[position][count].is_none_or(|(known, _)| freed > known) {
+                    best[position][count] = Some((freed, i));
+                }
+            };
+            offer(i + 1, count, freed);
+            if count < most {
+                offer(ends[i], count + 1, freed + sizes[i]);
+            }
+        }
+    }
+
+    let mut count = (0..=most)
+        .find(|&c| best[n][c].is_some_and(|(freed, _)| freed >= amount))
+        .ok_or_else(|| eyre!("no plan frees {amount} bytes"))?;
+    let mut picked = vec![];
+    let mut position = n;
+    while count > 0 {
+        let (freed, from) = best[position][count].unwrap();
+        if ends[from] == position
+            && best[from][count - 1].is_some_and(|(f, _)| f + sizes[from] == freed)
+        {
+            picked.push(from);
+            count -= 1;
+        }
+        position = from;
+    }
+    Ok(picked)
+}
+
+/// Entry names must stay within their directory.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        bail!("'{name}' is not a valid name");
+    }
+    Ok(())
+}
+
+/// Size in powers of 1024 as `du -h` shows it, rounded up: `584`, `9.5K`, `47M`.
+fn human(size: usize) -> String {
+    if size < 1024 {
+        return size.to_string();
+    }
+    let size = u128::try_from(size).unwrap();
+    let mut divisor = 1;
+    for unit in ['K', 'M', 'G', 'T', 'P', 'E'] {
+        divisor *= 1024;
+        let tenths = (size * 10).div_ceil(divisor);
+        if tenths < 100 {
+            return format!("{}.{}{unit}", tenths / 10, tenths % 10);
+        }
+        let whole = size.div_ceil(divisor);
+        if whole < 1024 {
+            return format!("{whole}{unit}");
+        }
+    }
+    unreachable!("sizes fit in 64 bits")
+}
+
+/// One line of a transcript.
+#[derive(Clone, Copy)]
+enum Line<'a> {
+    Cd(&'a str),
+    Ls,
+    Dir(&'a str),
+    File(usize, &'a str),
+}
+
+impl<'a> Line<'a> {
+    fn parse(s: &'a str) -> Result<Self> {
+        if let Some(command) = s.strip_prefix("$ ") {
+            return match command.split_whitespace().collect::<Vec<_>>()[..] {
+                ["cd", name] => Ok(Line::Cd(name)),
+                ["ls"] => Ok(Line::Ls),
+                _ => bail!("unknown command '{command}'"),
+            };
+        }
+        match s.split_once(' ') {
+            Some(("dir", name)) => Ok(Line::Dir(name)),
+            Some((size, name)) => Ok(Line::File(
+                size.parse()
+                    .wrap_err_with(|| format!("bad file size in '{s}'"))?,
+                name,
+            )),
+            None => bail!("cannot read '{s}'"),
+        }
+    }
+}
+
+/// An `ls` whose output is being read.
+struct Listing<'a> {
+    dir: DirId,
+    line: usize,
+    seen: BTreeSet<&'a str>,
+}
+
+/// Replays a transcript into a `FileSystem`.
+struct Shell<'a> {
+    fs: FileSystem,
+    current: DirId,
+    listing: Option<Listing<'a>>,
+}
+
+impl<'a> Shell<'a> {
+    fn end_listing(&mut self) -> Result<()> {
+        match self.listing.take() {
+            Some(l) => self
+                .fs
+                .end_listing(l.dir, &l.seen)
+                .wrap_err_with(|| format!("line {}", l.line)),
+            None => Ok(()),
+        }
+    }
+
+    fn run(&mut self, line: Line<'a>, number: usize) -> Result<()> {
+        match line {
+            Line::Cd("/") => self.current = FileSystem::ROOT,
+            Line::Cd("..") => {
+                self.current = self
+                    .fs
+                    .parent(self.current)
+                    .ok_or_else(|| eyre!("cd .. at the root"))?;
+            }
+            Line::Cd(name) => {
+                self.current = self.fs.child(self.current, name).ok_or_else(|| {
+                    eyre!("no directory {name} in {}", self.fs.path(self.current))
+                })?;
+            }
+            Line::Ls => {
+                self.listing = Some(Listing {
+                    dir: self.current,
+                    line: number,
+                    seen: BTreeSet::new(),
+                });
+            }
+            Line::Dir(name) | Line::File(_, name) => {
+                let Some(listing) = &mut self.listing else {
+                    bail!("output outside of ls");
+                };
+                let dir = self.fs.dir(self.current);
+                if dir.listed && !dir.files.contains_key(name) && !dir.dirs.contains_key(name) {
+                    bail!(
+                        "{} was not in the earlier listing",
+                        self.fs.entry_path(self.current, name)
+                    );
+                }
+                if let Line::File(size, _) = line {
+                    self.fs.add_file(self.current, name, size)?;
+                } else {
+                    self.fs.add_dir(self.current, name)?;
+                }
+                if !listing.seen.insert(name) {
+                    bail!("{} is listed twice", self.fs.entry_path(self.current, name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Follows the transcript, checking that it is consistent: directories are only
+/// entered once listed, and listing one again must give the same entries.
+pub fn parse(input: &[String]) -> Result<FileSystem> {
+    let mut shell = Shell {
+        fs: FileSystem::new(),
+        current: FileSystem::ROOT,
+        listing: None,
+    };
+    for (i, s) in input.iter().enumerate() {
+        let line = Line::parse(s).wrap_err_with(|| format!("line {}", i + 1))?;
+        if matches!(line, Line::Cd(_) | Line::Ls) {
+            shell.end_listing()?;
+        }
+        shell
+            .run(line, i + 1)
+            .wrap_err_with(|| format!("line {}", i + 1))?;
+    }
+    shell.end_listing()?;
+    Ok(shell.fs)
+}
+
+/// The transcript's filesystem drawn as a tree, see `FileSystem::tree`.
+pub fn tree(data: &[String], sizes: &RangeInclusive<usize>) -> Result<String> {
+    parse(data)?.tree(sizes)
+}
+
+/// The transcript's directories listed by size, see `FileSystem::du`.
+pub fn du(data: &[String], sizes: &RangeInclusive<usize>) -> Result<String> {
+    parse(data)?.du(sizes)
+}
+
+/// Total size of the directory at an absolute path such as `/a/e`.
+pub fn dir_size(data: &[String], path: &str) -> Result<usize> {
+    let fs = parse(data)?;
+    let id = fs
+        .lookup(path)
+        .ok_or_else(|| eyre!("no directory {path}"))?;
+    Ok(fs.size(id))
+}
+
+/// The transcript as `FileSystem::transcript` would write it.
+pub fn transcript(data: &[String]) -> Result<String> {
+    let mut out = parse(data)?.transcript().join("\n");
+    out.push('\n');
+    Ok(out)
+}
+
+pub fn solution1(data: &[String]) -> Result<usize> {
+    let fs = parse(data)?;
+    Ok(fs
+        .pre_order()
+        .map(|id| fs.size(id))
         .filter(|&x| x <= 100_000)
-        .sum()
+        .sum())
 }
 
-pub fn solution2(data: &[String]) -> usize {
-    let root = parse(data);
-    let dir_sizes: Vec<_> = all_dirs(&root)
-        .iter()
-        .map(|x| total_size(&Rc::clone(x)))
-        .collect();
-    let target_space = 70_000_000 - 30_000_000;
-    let amount_to_remove = dir_sizes.iter().max().unwrap() - target_space;
-    dir_sizes
-        .into_iter()
-        .filter(|&x| x >= amount_to_remove)
+/// Size of the smallest directory whose deletion leaves `needed` bytes free on a
+/// disk of `disk` bytes.
+pub fn smallest_deletion(data: &[String], disk: usize, needed: usize) -> Result<usize> {
+    let fs = parse(data)?;
+    let amount = fs.shortfall(disk, needed)?;
+    Ok(fs
+        .pre_order()
+        .map(|id| fs.size(id))
+        .filter(|&x| x >= amount)
         .min()
-        .unwrap()
+        .unwrap())
+}
+
+/// The directories to delete to have `needed` bytes free on a disk of `disk` bytes,
+/// e.g. `12 freed by deleting /x/w, /y`.
+pub fn plan_deletions(data: &[String], disk: usize, needed: usize, goal: Goal) -> Result<String> {
+    let fs = parse(data)?;
+    let (freed, dirs) = fs.plan_deletions(fs.shortfall(disk, needed)?, goal)?;
+    if dirs.is_empty() {
+        return Ok("nothing to delete".into());
+    }
+    let paths: Vec<_> = dirs.into_iter().map(|id| fs.path(id)).collect();
+    Ok(format!("{freed} freed by deleting {}", paths.join(", ")))
+}
+
+pub fn solution2(data: &[String]) -> Result<usize> {
+    smallest_deletion(data, 70_000_000, 30_000_000)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{human, FileSystem, Goal};
     use crate::{common::str2lines, day7};
 
     fn data() -> Vec<String> {
@@ -148,13 +843,258 @@ $ ls
         )
     }
 
+    #[test]
+    fn test_navigation() {
+        let fs = day7::parse(&data()).unwrap();
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!("/a/e", fs.path(e));
+        assert_eq!(584, fs.size(e));
+        assert_eq!(fs.lookup("/a"), fs.parent(e));
+        assert_eq!(Some(e), fs.child(fs.lookup("/a").unwrap(), "e"));
+        assert_eq!(Some(FileSystem::ROOT), fs.lookup("/"));
+        assert_eq!(None, fs.lookup("/a/x"));
+        assert_eq!(None, fs.lookup("a"));
+        let names: Vec<_> = fs
+            .children(FileSystem::ROOT)
+            .map(|id| fs.path(id))
+            .collect();
+        assert_eq!(vec!["/a", "/d"], names);
+        assert_eq!(48_381_165, fs.size(FileSystem::ROOT));
+    }
+
+    #[test]
+    fn test_dir_size() {
+        assert_eq!(584, day7::dir_size(&data(), "/a/e").unwrap());
+        assert_eq!(94853, day7::dir_size(&data(), "/a/").unwrap());
+        assert_eq!(
+            "no directory /a/i",
+            day7::dir_size(&data(), "/a/i").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let fs = day7::parse(&data()).unwrap();
+        let paths = |ids: Vec<_>| ids.into_iter().map(|id| fs.path(id)).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["/", "/a", "/a/e", "/d"],
+            paths(fs.pre_order().collect())
+        );
+        assert_eq!(
+            vec!["/a/e", "/a", "/d", "/"],
+            paths(fs.post_order().collect())
+        );
+    }
+
     #[test]
     fn test_solution1() {
-        assert_eq!(95437, day7::solution1(&data()));
+        assert_eq!(95437, day7::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(24_933_642, day7::solution2(&data()));
+        assert_eq!(24_933_642, day7::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_revisit() {
+        let mut lines = data();
+        lines.extend(str2lines("$ cd /\n$ ls\ndir d\n8504156 c.dat\ndir a\n14848514 b.txt\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e"));
+        let fs = day7::parse(&lines).unwrap();
+        assert_eq!(48_381_165, fs.size(FileSystem::ROOT));
+        assert_eq!(95437, day7::solution1(&lines).unwrap());
+    }
+
+    #[test]
+    fn test_bad_transcripts() {
+        let cases = [
+            ("$ cd /\n$ cd x", "line 2", "no directory x in /"),
+            (
+                "$ ls\ndir a\n$ cd a\n$ cd ..\n$ cd ..",
+                "line 5",
+                "cd .. at the root",
+            ),
+            (
+                "$ ls\n10 a\n$ ls\n20 a",
+                "line 4",
+                "/a was 10 bytes and is now 20",
+            ),
+            ("$ ls\ndir a\n10 a", "line 3", "/a is a directory"),
+            ("$ ls\n10 a\ndir a", "line 3", "/a is a file"),
+            ("$ ls\n10 a\n$ ls\ndir a", "line 4", "/a is a file"),
+            ("$ ls\n10 a\n10 a", "line 3", "/a is listed twice"),
+            ("$ ls\ndir a\ndir a", "line 3", "/a is listed twice"),
+            (
+                "$ ls\n10 a\n$ ls\n10 a\n5 b",
+                "line 5",
+                "/b was not in the earlier listing",
+            ),
+            (
+                "$ ls\ndir a\n$ cd a\n$ ls\n1 b\n1 c\n$ ls\n1 c",
+                "line 7",
+                "listing of /a is missing b",
+            ),
+            ("$ cd /\n10 a", "line 2", "output outside of ls"),
+            ("$ rm -rf /", "line 1", "unknown command 'rm -rf /'"),
+            ("$ ls\nx a", "line 2", "bad file size in 'x a'"),
+            ("$ ls\n1 ../../x", "line 2", "'../../x' is not a valid name"),
+            ("$ ls\ndir ..", "line 2", "'..' is not a valid name"),
+            ("$ ls\ndir .", "line 2", "'.' is not a valid name"),
+            (
+                "$ ls\n1 /etc/foo",
+                "line 2",
+                "'/etc/foo' is not a valid name",
+            ),
+        ];
+        for (transcript, context, cause) in cases {
+            let err = day7::solution1(&str2lines(transcript)).unwrap_err();
+            assert_eq!(context, err.to_string(), "{transcript}");
+            let chain: Vec<_> = err.chain().map(ToString::to_string).collect();
+            assert!(
+                chain.contains(&cause.to_string()),
+                "{transcript}: {chain:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_eq!(
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+",
+            day7::tree(&data(), &(0..=usize::MAX)).unwrap()
+        );
+        assert_eq!(
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+",
+            day7::tree(&data(), &(0..=1000)).unwrap()
+        );
+        assert_eq!("", day7::tree(&data(), &(1..=2)).unwrap());
+    }
+
+    #[test]
+    fn test_du() {
+        assert_eq!(
+            "47M   /
+24M   /d
+93K   /a
+584   /a/e
+",
+            day7::du(&data(), &(0..=usize::MAX)).unwrap()
+        );
+        assert_eq!(
+            "24M   /d
+93K   /a
+",
+            day7::du(&data(), &(1000..=30_000_000)).unwrap()
+        );
+        assert_eq!("1.0K", human(1024));
+        assert_eq!("9.6K", human(9800));
+        assert_eq!("10K", human(10_240));
+        assert_eq!("1.0M", human(1_048_575));
+        assert_eq!("16E", human(usize::MAX));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fs = day7::parse(&data()).unwrap();
+        let transcript = fs.transcript();
+        assert_eq!(
+            vec![
+                "$ cd /",
+                "$ ls",
+                "dir a",
+                "14848514 b.txt",
+                "8504156 c.dat",
+                "dir d"
+            ],
+            transcript[..6]
+        );
+        assert_eq!(transcript, day7::parse(&transcript).unwrap().transcript());
+
+        let root = std::env::temp_dir().join(format!("aoc2022-day7-{}", std::process::id()));
+        fs.materialize(&root).unwrap();
+        let scanned = FileSystem::scan(&root);
+        let again = fs.materialize(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(transcript, scanned.unwrap().transcript());
+        assert!(again.is_err());
+    }
+
+    #[test]
+    fn test_plan_deletions() {
+        let small = str2lines(
+            "$ cd /\n$ ls\ndir x\ndir y\ndir z\n$ cd x\n$ ls\ndir w\n10 f\n$ cd w\n$ ls\n5 g\n\
+             $ cd /\n$ cd y\n$ ls\n7 h\n$ cd ..\n$ cd z\n$ ls\n9 i",
+        );
+        let cases = [
+            (20, Goal::Excess, "12 freed by deleting /x/w, /y"),
+            (20, Goal::Deletions, "15 freed by deleting /x"),
+            (25, Goal::Excess, "16 freed by deleting /y, /z"),
+            (25, Goal::Deletions, "16 freed by deleting /y, /z"),
+            (40, Goal::Excess, "31 freed by deleting /x, /y, /z"),
+            (40, Goal::Deletions, "31 freed by deleting /x, /y, /z"),
+            (9, Goal::Excess, "nothing to delete"),
+        ];
+        for (needed, goal, plan) in cases {
+            assert_eq!(
+                plan,
+                day7::plan_deletions(&small, 40, needed, goal).unwrap(),
+                "{needed} {goal:?}"
+            );
+        }
+        assert_eq!(15, day7::smallest_deletion(&small, 40, 20).unwrap());
+        assert_eq!(
+            "cannot have 50 bytes free on a disk of 40",
+            day7::smallest_deletion(&small, 40, 50)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "31 bytes do not fit on a disk of 30",
+            day7::plan_deletions(&small, 30, 0, Goal::Excess)
+                .unwrap_err()
+                .to_string()
+        );
+        // too many bytes to track one by one
+        let big = str2lines(
+            "$ ls\ndir x\ndir y\n$ cd x\n$ ls\n60000001 f\n$ cd ..\n$ cd y\n$ ls\n20000003 g",
+        );
+        for goal in [Goal::Excess, Goal::Deletions] {
+            assert_eq!(
+                "80000004 freed by deleting /x, /y",
+                day7::plan_deletions(&big, 100_000_000, 90_000_000, goal).unwrap()
+            );
+            assert_eq!(
+                "60000001 freed by deleting /x",
+                day7::plan_deletions(&big, 100_000_000, 50_000_000, goal).unwrap()
+            );
+        }
+        assert_eq!(
+            "24933642 freed by deleting /d",
+            day7::plan_deletions(&data(), 70_000_000, 30_000_000, Goal::Excess).unwrap()
+        );
+        assert_eq!(
+            "deleting every directory frees only 25028495 of the 48381165 bytes needed",
+            day7::plan_deletions(&data(), 50_000_000, 50_000_000, Goal::Deletions)
+                .unwrap_err()
+                .to_string()
+        );
     }
 }
@@ -1,111 +1,307 @@
-use std::{cmp::max, collections::HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
 
-use crate::common::transpose;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
 
-fn parse(data: &[String]) -> Vec<Vec<u8>> {
-    data.iter().map(|row| row.as_bytes().into()).collect()
+use crate::common::parse::parse_num;
+use crate::common::render::{Canvas, Rgb};
+
+/// Directions to look in, clockwise from north.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
 }
 
-/// Returns vector index after which it stops being monotonously increasing.
-fn visible_l<T: PartialOrd>(arr: &[T]) -> HashSet<usize> {
-    // panics if v is empty but we don't care
-    let mut max = &arr[0];
-    let mut result = HashSet::from([0]);
-    for (i, el) in arr.iter().enumerate().skip(1) {
-        if el > max {
-            max = el;
-            result.insert(i);
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// Change in x and y per step, y growing southwards.
+    fn step(self) -> (i64, i64) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::NorthEast => (1, -1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, 1),
+            Direction::South => (0, 1),
+            Direction::SouthWest => (-1, 1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::NorthEast => "north-east",
+            Direction::East => "east",
+            Direction::SouthEast => "south-east",
+            Direction::South => "south",
+            Direction::SouthWest => "south-west",
+            Direction::West => "west",
+            Direction::NorthWest => "north-west",
         }
     }
-    result
 }
 
-/// Check visibility for both sides of a tree row.
-fn visible_lr<T: PartialOrd>(arr: &[T]) -> HashSet<usize> {
-    let left = visible_l(arr);
-    let reversed: Vec<_> = arr.iter().rev().collect();
-    let right = visible_l(&reversed)
-        .iter()
-        .map(|x| arr.len() - x - 1)
-        .collect();
-    left.union(&right).copied().collect()
+/// What a heatmap colours the trees by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heatmap {
+    Visibility,
+    Scenic,
 }
 
-fn scenic_score_l<T: PartialOrd>(arr: &[T], idx: usize) -> usize {
-    let val = &arr[idx];
-    let mut count = 0;
-    for i in (0..idx).rev() {
-        count += 1;
-        if &arr[i] >= val {
-            break;
-        }
+/// The steps `k` for which `v + k * d` lies within `0..len`, or `None` if there are none.
+/// Worked out in `i128`, where no observer position can overflow.
+fn steps_within(v: i64, d: i64, len: usize) -> Option<(i128, i128)> {
+    let (v, last) = (i128::from(v), i128::try_from(len).ok()? - 1);
+    match d {
+        1.. => Some((-v, last - v)),
+        ..0 => Some((v - last, v)),
+        0 => (0..=last).contains(&v).then_some((i128::MIN, i128::MAX)),
     }
-    count
 }
 
-fn scenic_score_lr<T: PartialOrd>(arr: &[T], idx: usize) -> usize {
-    let l = scenic_score_l(arr, idx);
-    let x: Vec<_> = arr.iter().rev().collect();
-    let r = scenic_score_l(&x, arr.len() - idx - 1);
-    l * r
+/// Tree heights row by row.
+pub struct Forest {
+    width: usize,
+    height: usize,
+    trees: Vec<u32>,
 }
 
-fn scenic_score_line_lr<T: PartialOrd>(line: &[T]) -> Vec<usize> {
-    let mut result = Vec::new();
-    for i in 0..line.len() {
-        result.push(scenic_score_lr(line, i));
+impl Forest {
+    /// Heights are one digit per tree, or separated by commas or whitespace if any
+    /// row has a separator between its heights, which allows heights beyond 9.
+    pub fn parse(data: &[String]) -> Result<Self> {
+        let rows: Vec<&str> = data.iter().map(|row| row.trim()).collect();
+        let separated = rows
+            .iter()
+            .any(|row| row.contains(|c: char| c == ',' || c.is_whitespace()));
+        let mut trees = vec![];
+        let mut width = None;
+        for (i, row) in rows.iter().enumerate() {
+            let heights: Result<Vec<u32>> = if separated {
+                separated_heights(row)
+            } else {
+                (row.chars())
+                    .map(|c| c.to_digit(10).ok_or_else(|| eyre!("'{c}' is not a height")))
+                    .collect()
+            };
+            let heights = heights.wrap_err_with(|| format!("line {}", i + 1))?;
+            match width {
+                None => width = Some(heights.len()),
+                Some(w) if w != heights.len() => {
+                    bail!(
+                        "line {} has {} trees but line 1 has {w}",
+                        i + 1,
+                        heights.len()
+                    );
+                }
+                Some(_) => {}
+            }
+            trees.extend(heights);
+        }
+        Ok(Self {
+            width: width.unwrap_or(0),
+            height: data.len(),
+            trees,
+        })
+    }
+
+    /// Runs `f` along every line of sight into the forest, from each of its edges,
+    /// with the indices of the trees in the order they are passed.
+    fn for_each_line(&self, mut f: impl FnMut(&mut dyn Iterator<Item = usize>)) {
+        let (w, h) = (self.width, self.height);
+        for r in 0..h {
+            let row = r * w..(r + 1) * w;
+            f(&mut row.clone());
+            f(&mut row.rev());
+        }
+        for c in 0..w {
+            let column = (c..w * h).step_by(w);
+            f(&mut column.clone());
+            f(&mut column.rev());
+        }
+    }
+
+    /// Whether each tree can be seen from outside the forest.
+    fn visible(&self) -> Vec<bool> {
+        let mut visible = vec![false; self.trees.len()];
+        self.for_each_line(|line| mark_visible(&self.trees, line, &mut visible));
+        visible
+    }
+
+    /// Product of each tree's viewing distances in the four directions.
+    fn scenic_scores(&self) -> Vec<usize> {
+        let mut scores = vec![1; self.trees.len()];
+        self.for_each_line(|line| multiply_distances(&self.trees, line, &mut scores));
+        scores
+    }
+
+    /// Trees seen looking from `(x, y)` towards `direction`, nearest first: those
+    /// taller than every tree between them and the observer. The observer may stand
+    /// outside the forest; a tree where they stand is not counted.
+    pub fn visible_from(&self, (x, y): (i64, i64), direction: Direction) -> Vec<(usize, usize)> {
+        let (dx, dy) = direction.step();
+        let mut seen = vec![];
+        let (Some((x_first, x_last)), Some((y_first, y_last))) = (
+            steps_within(x, dx, self.width),
+            steps_within(y, dy, self.height),
+        ) else {
+            return seen;
+        };
+        let mut tallest = None;
+        for k in x_first.max(y_first).max(1)..=x_last.min(y_last) {
+            let px = usize::try_from(i128::from(x) + k * i128::from(dx)).unwrap();
+            let py = usize::try_from(i128::from(y) + k * i128::from(dy)).unwrap();
+            let tree = self.trees[py * self.width + px];
+            if tallest.is_none_or(|t| tree > t) {
+                seen.push((px, py));
+                tallest = Some(tree);
+            }
+        }
+        seen
+    }
+
+    /// Every tree drawn as its height, `+` above 9, coloured by `kind`. Scenic scores go from
+    /// blue to yellow on a square root scale, so that more than the best few show.
+    pub fn heatmap(&self, kind: Heatmap) -> Canvas<(char, Rgb)> {
+        let colors: Vec<Rgb> = match kind {
+            Heatmap::Visibility => self
+                .visible()
+                .into_iter()
+                .map(|v| if v { [120, 220, 80] } else { [30, 70, 30] })
+                .collect(),
+            Heatmap::Scenic => {
+                let scores = self.scenic_scores();
+                let max = u128::try_from(scores.iter().copied().max().unwrap_or(0).max(1)).unwrap();
+                scores
+                    .into_iter()
+                    .map(|score| {
+                        let score = u128::try_from(score).unwrap();
+                        let level = u8::try_from((score * 255 * 255 / max).isqrt()).unwrap();
+                        [level, level, 255 - level]
+                    })
+                    .collect()
+            }
+        };
+        let glyphs = (self.trees.iter()).map(|&t| char::from_digit(t, 10).unwrap_or('+'));
+        Canvas::new(self.width, self.height, glyphs.zip(colors).collect())
     }
-    result
 }
 
-fn scenic_score_matrix_lr<T: PartialOrd>(arr: &[Vec<T>]) -> Vec<Vec<usize>> {
-    arr.iter().map(|line| scenic_score_line_lr(line)).collect()
+/// Heights split on the row's commas, or else on its whitespace.
+fn separated_heights(row: &str) -> Result<Vec<u32>> {
+    let tokens: Vec<&str> = if row.contains(',') {
+        row.split(',').map(str::trim).collect()
+    } else {
+        row.split_whitespace().collect()
+    };
+    if let [token] = tokens[..] {
+        if token.len() > 1 {
+            bail!("'{token}' is a row of digits among separated heights");
+        }
+    }
+    tokens.into_iter().map(parse_num).collect()
 }
 
-pub fn solution1(data: &[String]) -> usize {
-    let input = parse(data);
-    let mut result = HashSet::new();
+/// Marks the trees along the line that are taller than all before them.
+fn mark_visible<T: Ord + Copy>(
+    trees: &[T],
+    line: &mut dyn Iterator<Item = usize>,
+    visible: &mut [bool],
+) {
+    let mut tallest = None;
+    for i in line {
+        if tallest.is_none_or(|t| trees[i] > t) {
+            visible[i] = true;
+            tallest = Some(trees[i]);
+        }
+    }
+}
 
-    for (j, line) in input.iter().enumerate() {
-        let coords = visible_lr(line);
-        for x in coords {
-            result.insert((x, j));
+/// Multiplies each tree's score by how far it sees back along the line, up to the
+/// first tree at least as tall or the edge.
+fn multiply_distances<T: Ord + Copy>(
+    trees: &[T],
+    line: &mut dyn Iterator<Item = usize>,
+    scores: &mut [usize],
+) {
+    // trees not yet blocked by a taller one later on, tallest at the bottom
+    let mut stack: Vec<(usize, T)> = vec![];
+    for (position, i) in line.enumerate() {
+        let height = trees[i];
+        while stack.last().is_some_and(|&(_, h)| h < height) {
+            stack.pop();
         }
+        scores[i] *= stack.last().map_or(position, |&(p, _)| position - p);
+        stack.push((position, height));
     }
+}
 
-    for (i, row) in transpose(&input).iter().enumerate() {
-        let coords = visible_lr(row);
-        for x in coords {
-            result.insert((i, x));
+/// Trees seen in each direction from `from`, if given, then the heatmap in colour.
+pub fn report(data: &[String], from: Option<(i64, i64)>, kind: Heatmap) -> Result<String> {
+    let forest = Forest::parse(data)?;
+    let mut out = String::new();
+    if let Some(from) = from {
+        for direction in Direction::ALL {
+            let seen: Vec<_> = (forest.visible_from(from, direction).iter())
+                .map(|(x, y)| format!("{x},{y}"))
+                .collect();
+            let seen = if seen.is_empty() {
+                "none".into()
+            } else {
+                seen.join(" ")
+            };
+            writeln!(out, "{:<10} {seen}", direction.name())?;
         }
+        writeln!(out)?;
     }
+    writeln!(out, "{}", forest.heatmap(kind).ansi(|c| c.0, |c| c.1))?;
+    Ok(out)
+}
 
-    result.len()
+/// The heatmap as a PNG image, each tree a 4x4 square.
+pub fn write_heatmap(data: &[String], kind: Heatmap, w: impl Write) -> Result<()> {
+    Forest::parse(data)?.heatmap(kind).write_png(w, 4, |c| c.1)
 }
 
-pub fn solution2(data: &[String]) -> usize {
-    let input = parse(data);
-    let horiz: Vec<usize> = scenic_score_matrix_lr(&input).into_iter().flatten().collect();
-    let transposed = transpose(&input);
-    let transposed_vert = scenic_score_matrix_lr(&transposed);
-    // a bit of a mess since I decided to make transpose return &[&T]
-    let vert: Vec<usize> = transpose(&transposed_vert)
-        .iter()
-        .flatten()
-        .copied()
-        .copied()
-        .collect();
-    let mut result = 0;
-    for (i, x) in horiz.into_iter().enumerate() {
-        result = max(result, x * vert[i]);
-    }
-    result
+pub fn solution1(data: &[String]) -> Result<usize> {
+    Ok(Forest::parse(data)?
+        .visible()
+        .into_iter()
+        .filter(|&v| v)
+        .count())
+}
+
+pub fn solution2(data: &[String]) -> Result<usize> {
+    Ok(Forest::parse(data)?
+        .scenic_scores()
+        .into_iter()
+        .max()
+        .unwrap_or(0))
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
+    use super::{mark_visible, multiply_distances, Direction, Forest, Heatmap};
     use crate::{common::str2lines, day8};
 
     fn data() -> Vec<String> {
@@ -118,29 +314,167 @@ mod tests {
         )
     }
 
+    /// Trees in a row visible from its left or right end.
+    fn visible_lr(row: &[u8]) -> HashSet<usize> {
+        let mut visible = vec![false; row.len()];
+        mark_visible(row, &mut (0..row.len()), &mut visible);
+        mark_visible(row, &mut (0..row.len()).rev(), &mut visible);
+        (0..row.len()).filter(|&i| visible[i]).collect()
+    }
+
+    /// Scenic score of a tree in a row looking left and right only.
+    fn scenic_score_lr(row: &[u8], idx: usize) -> usize {
+        let mut scores = vec![1; row.len()];
+        multiply_distances(row, &mut (0..row.len()), &mut scores);
+        multiply_distances(row, &mut (0..row.len()).rev(), &mut scores);
+        scores[idx]
+    }
+
     #[test]
     fn test_visible_lr() {
-        assert_eq!(HashSet::from([0]), day8::visible_lr(b"5"));
-        assert_eq!(HashSet::from([0, 2]), day8::visible_lr(b"515"));
-        assert_eq!(HashSet::from([0, 1, 2, 4]), day8::visible_lr(b"25512"));
+        assert_eq!(HashSet::from([0]), visible_lr(b"5"));
+        assert_eq!(HashSet::from([0, 2]), visible_lr(b"515"));
+        assert_eq!(HashSet::from([0, 1, 2, 4]), visible_lr(b"25512"));
     }
 
     #[test]
     #[allow(clippy::identity_op)]
     fn test_scenic_score_left_and_right() {
-        assert_eq!(0, day8::scenic_score_lr(b"33549", 0));
-        assert_eq!(2 * 2, day8::scenic_score_lr(b"33549", 2));
-        assert_eq!(2 * 1, day8::scenic_score_lr(b"35353", 3));
-        assert_eq!(1 * 1, day8::scenic_score_lr(b"95653", 3));
+        assert_eq!(0, scenic_score_lr(b"33549", 0));
+        assert_eq!(2 * 2, scenic_score_lr(b"33549", 2));
+        assert_eq!(2 * 1, scenic_score_lr(b"35353", 3));
+        assert_eq!(1 * 1, scenic_score_lr(b"95653", 3));
+    }
+
+    #[test]
+    fn test_rectangular() {
+        // walking out from every tree, in a forest that is not square
+        let forest = Forest::parse(&str2lines("3037\n2551\n6533")).unwrap();
+        let (w, h) = (4, 3);
+        let at = |x: usize, y: usize| forest.trees[y * w + x];
+        let mut visible = vec![];
+        let mut scores = vec![];
+        for y in 0..h {
+            for x in 0..w {
+                let views: [Vec<_>; 4] = [
+                    (0..x).rev().map(|x1| at(x1, y)).collect(),
+                    (x + 1..w).map(|x1| at(x1, y)).collect(),
+                    (0..y).rev().map(|y1| at(x, y1)).collect(),
+                    (y + 1..h).map(|y1| at(x, y1)).collect(),
+                ];
+                let tree = at(x, y);
+                visible.push(views.iter().any(|v| v.iter().all(|&t| t < tree)));
+                scores.push(
+                    views
+                        .iter()
+                        .map(|v| v.iter().position(|&t| t >= tree).map_or(v.len(), |p| p + 1))
+                        .product::<usize>(),
+                );
+            }
+        }
+        assert_eq!(visible, forest.visible());
+        assert_eq!(scores, forest.scenic_scores());
     }
 
     #[test]
     fn test_solution1() {
-        assert_eq!(21, day8::solution1(&data()));
+        assert_eq!(21, day8::solution1(&data()).unwrap());
     }
 
     #[test]
     fn test_solution2() {
-        assert_eq!(8, day8::solution2(&data()));
+        assert_eq!(8, day8::solution2(&data()).unwrap());
+    }
+
+    #[test]
+    fn test_visible_from() {
+        let forest = Forest::parse(&data()).unwrap();
+        let cases = [
+            ((2, 2), Direction::North, vec![(2, 1)]),
+            ((-1, 1), Direction::East, vec![(0, 1), (1, 1)]),
+            ((0, 4), Direction::NorthEast, vec![(1, 3)]),
+            ((-1, -1), Direction::SouthEast, vec![(0, 0), (1, 1)]),
+            ((5, -1), Direction::SouthWest, vec![(4, 0)]),
+            ((10, 0), Direction::North, vec![]),
+            ((-3, 0), Direction::NorthEast, vec![]),
+            ((i64::MAX, 0), Direction::West, vec![(4, 0), (3, 0)]),
+            ((i64::MIN, 1), Direction::East, vec![(0, 1), (1, 1)]),
+            (
+                (i64::MIN, i64::MIN),
+                Direction::SouthEast,
+                vec![(0, 0), (1, 1)],
+            ),
+            ((i64::MIN, i64::MAX), Direction::NorthEast, vec![]),
+        ];
+        for (from, direction, seen) in cases {
+            assert_eq!(
+                seen,
+                forest.visible_from(from, direction),
+                "{from:?} {direction:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let forest = Forest::parse(&data()).unwrap();
+        let visibility = forest.heatmap(Heatmap::Visibility);
+        assert_eq!(data().join("\n"), visibility.ascii(|c| c.0));
+        assert_eq!([30, 70, 30], visibility.get(3, 3).1);
+        assert_eq!([120, 220, 80], visibility.get(1, 1).1);
+        let scenic = forest.heatmap(Heatmap::Scenic);
+        assert_eq!([255, 255, 0], scenic.get(2, 3).1);
+        assert_eq!([0, 0, 255], scenic.get(0, 0).1);
+    }
+
+    #[test]
+    fn test_report() {
+        let report = day8::report(&data(), Some((-1, 1)), Heatmap::Visibility).unwrap();
+        let lines: Vec<_> = report.lines().take(3).collect();
+        assert_eq!(
+            vec!["north      none", "north-east 0,0", "east       0,1 1,1"],
+            lines
+        );
+        assert!(report.contains("\x1b[38;2;120;220;80m"));
+    }
+
+    #[test]
+    fn test_separated_heights() {
+        let spaced = str2lines("3 0 3 7 3\n2 5 5 1 2\n6  5 3 3 2\n3,3,5,4,9\n3, 5, 3, 9, 0");
+        assert_eq!(21, day8::solution1(&spaced).unwrap());
+        assert_eq!(8, day8::solution2(&spaced).unwrap());
+
+        // trailing whitespace does not make a forest of digits separated
+        let padded: Vec<_> = data().iter().map(|row| format!("{row} \t")).collect();
+        assert_eq!(21, day8::solution1(&padded).unwrap());
+        assert_eq!(8, day8::solution2(&padded).unwrap());
+
+        let tall = str2lines("10 2 30\n4 25 6\n7 8 9");
+        let forest = Forest::parse(&tall).unwrap();
+        assert_eq!(25, forest.trees[4]);
+        assert_eq!(9, day8::solution1(&tall).unwrap());
+        assert_eq!(
+            "+2+\n4+6\n789",
+            forest.heatmap(Heatmap::Visibility).ascii(|c| c.0)
+        );
+    }
+
+    #[test]
+    fn test_bad_forests() {
+        let err = day8::solution1(&str2lines("123\n45")).unwrap_err();
+        assert_eq!("line 2 has 2 trees but line 1 has 3", err.to_string());
+        let err = day8::solution1(&str2lines("1 2\n3 4 5")).unwrap_err();
+        assert_eq!("line 2 has 3 trees but line 1 has 2", err.to_string());
+        let err = day8::solution1(&str2lines("12\n3x")).unwrap_err();
+        assert_eq!("line 2", err.to_string());
+        assert_eq!("'x' is not a height", err.root_cause().to_string());
+        let err = day8::solution2(&str2lines("1,2\n3,-4")).unwrap_err();
+        assert_eq!("line 2", err.to_string());
+        let err = day8::solution1(&str2lines("1 2\n34")).unwrap_err();
+        assert_eq!("line 2", err.to_string());
+        assert_eq!(
+            "'34' is a row of digits among separated heights",
+            err.root_cause().to_string()
+        );
     }
 }
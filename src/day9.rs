@@ -1,5 +1,8 @@
 use std::collections::HashSet;
 
+use crate::common::render::{Canvas, Rgb};
+use crate::simulation::Simulation;
+
 enum Axis {
     X,
     Y,
@@ -45,66 +48,121 @@ fn parse(data: &[String]) -> Vec<Command> {
         .collect()
 }
 
-fn tail_visited<const N: usize>(commands: &[Command]) -> HashSet<Coords> {
-    let mut rope = [Coords(0, 0); N];
-    let mut result = HashSet::new();
-    for cmd in commands {
+/// Rope of `N` knots following the commands one step at a time.
+pub struct Rope<const N: usize> {
+    commands: Vec<Command>,
+    command_idx: usize,
+    command_steps: i32,
+    knots: [Coords; N],
+    visited: HashSet<Coords>,
+    /// Corners of the area the head ever reaches, the rest of the rope stays inside.
+    bounds: (Coords, Coords),
+}
+
+impl<const N: usize> Rope<N> {
+    fn new(commands: Vec<Command>) -> Self {
+        let mut head = Coords(0, 0);
+        let (mut min, mut max) = (head, head);
+        for cmd in &commands {
+            for _ in 0..cmd.1.abs() {
+                head.step(cmd);
+                min = Coords(min.0.min(head.0), min.1.min(head.1));
+                max = Coords(max.0.max(head.0), max.1.max(head.1));
+            }
+        }
+        Self {
+            commands,
+            command_idx: 0,
+            command_steps: 0,
+            knots: [Coords(0, 0); N],
+            visited: HashSet::new(),
+            bounds: (min, max),
+        }
+    }
+
+    /// Moves the head by one step and the rest of the rope after it.
+    fn move_head(&mut self) -> bool {
         // moving head all the way and then tracing step by step also works
         // but let's do both step by step anyway
-        for _ in 0..cmd.1.abs() {
-            for i in 0..rope.len() {
-                if i == 0 {
-                    rope[i].step(cmd);
-                } else {
-                    let prev = rope[i - 1];
-                    rope[i].move_tail(prev);
-                }
-                if i == rope.len() - 1 {
-                    result.insert(rope[i]);
-                }
+        while let Some(cmd) = self.commands.get(self.command_idx) {
+            if self.command_steps < cmd.1.abs() {
+                break;
             }
+            self.command_idx += 1;
+            self.command_steps = 0;
+        }
+        let Some(cmd) = self.commands.get(self.command_idx) else {
+            return false;
+        };
+        self.knots[0].step(cmd);
+        for i in 1..N {
+            let prev = self.knots[i - 1];
+            self.knots[i].move_tail(prev);
         }
+        self.visited.insert(self.knots[N - 1]);
+        self.command_steps += 1;
+        true
     }
-    result
 }
 
-#[allow(dead_code)]
-fn draw(data: &HashSet<Coords>) -> Vec<String> {
-    let mut result = vec![];
-    let min_x = data.iter().map(|x| x.0).min().unwrap();
-    let min_y = data.iter().map(|x| x.1).min().unwrap();
-    let max_x = data.iter().map(|x| x.0).max().unwrap();
-    let max_y = data.iter().map(|x| x.1).max().unwrap();
-    for y in (min_y..=max_y).rev() {
-        let mut line = String::new();
-        for x in min_x..=max_x {
-            let c = if data.contains(&Coords(x, y)) {
-                '#'
-            } else {
-                '.'
-            };
-            line.push(c);
-        }
-        result.push(line);
+impl<const N: usize> Simulation for Rope<N> {
+    fn step(&mut self) -> bool {
+        self.move_head()
+    }
+
+    fn frame(&self) -> Canvas<u8> {
+        let (min, max) = self.bounds;
+        // y grows upwards here, unlike on screen; the corners keep every frame the same size
+        let corners = [(min.0, -max.1), (max.0, -min.1)].map(|c| (c, 0));
+        let knots = self.knots.iter().rev().map(|c| (c, 3));
+        let points = (self.visited.iter().map(|c| (c, 1)))
+            .chain([(&Coords(0, 0), 2)])
+            .chain(knots)
+            .chain([(&self.knots[0], 4)])
+            .map(|(c, value)| ((c.0, -c.1), value));
+        Canvas::from_sparse(corners.into_iter().chain(points), 0).0
     }
-    result
+
+    fn palette(&self) -> Vec<(char, Rgb)> {
+        vec![
+            ('.', [40, 40, 40]),
+            ('#', [60, 140, 60]),
+            ('s', [220, 200, 40]),
+            ('o', [230, 230, 230]),
+            ('H', [220, 40, 40]),
+        ]
+    }
+}
+
+fn tail_visited<const N: usize>(commands: Vec<Command>) -> HashSet<Coords> {
+    let mut rope = Rope::<N>::new(commands);
+    while rope.move_head() {}
+    rope.visited
 }
 
 pub fn solution1(data: &[String]) -> usize {
     let commands = parse(data);
-    let data = tail_visited::<2>(&commands);
+    let data = tail_visited::<2>(commands);
     data.len()
 }
 
 pub fn solution2(data: &[String]) -> usize {
     let commands = parse(data);
-    let data = tail_visited::<10>(&commands);
+    let data = tail_visited::<10>(commands);
     data.len()
 }
 
+pub fn simulation1(data: &[String]) -> Rope<2> {
+    Rope::new(parse(data))
+}
+
+pub fn simulation2(data: &[String]) -> Rope<10> {
+    Rope::new(parse(data))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{common::str2lines, day9};
+    use crate::{common::str2lines, day9, simulation::Simulation};
 
     fn data() -> Vec<String> {
         str2lines(
@@ -137,6 +195,23 @@ U 20"#,
         assert_eq!(13, day9::solution1(&data()));
     }
 
+    #[test]
+    fn test_frame() {
+        let mut rope = day9::simulation1(&data());
+        for _ in 0..6 {
+            rope.step();
+        }
+        assert_eq!(
+            "......\n......\n....H.\n....o.\ns###..",
+            rope.frame().ascii(|&c| rope.palette()[usize::from(c)].0)
+        );
+        while rope.step() {}
+        assert_eq!(
+            "..##..\n...##.\n.oH##.\n....#.\ns###..",
+            rope.frame().ascii(|&c| rope.palette()[usize::from(c)].0)
+        );
+    }
+
     #[test]
     fn test_solution2() {
         assert_eq!(1, day9::solution2(&data()));
@@ -1,7 +1,11 @@
 #![warn(clippy::pedantic)]
-use clap::{command, Parser};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
 use color_eyre::eyre::Result;
-use solution::Solution;
+use solution::{Options, Solution};
 
 mod common;
 mod day1;
@@ -21,28 +25,88 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod simulation;
 mod solution;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// AOC day
-    day: u8,
+    #[arg(required = true)]
+    day: Option<u8>,
     /// AOC solution part
-    part: u8,
+    #[arg(required = true)]
+    part: Option<u8>,
+    #[command(flatten)]
+    options: Options,
 }
 
-impl Args {
-    pub fn solution(&self) -> Solution {
-        Solution::new(self.day, self.part)
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Animate a puzzle step by step
+    Visualize {
+        /// AOC day
+        day: u8,
+        /// AOC solution part
+        part: u8,
+        /// Frames per second
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        fps: u32,
+        /// Stop after this many frames, required for day 17 part 2
+        #[arg(long)]
+        max_frames: Option<usize>,
+        /// Write an animated GIF instead of playing in the terminal
+        #[arg(long, conflicts_with = "cast")]
+        gif: Option<PathBuf>,
+        /// Pixels per cell in the GIF
+        #[arg(
+            long,
+            default_value_t = 4,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        scale: usize,
+        /// Write an asciicast recording instead of playing in the terminal
+        #[arg(long)]
+        cast: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
     let args = Args::parse();
-    println!("{}", args.solution().solve());
+    match args.command {
+        None => {
+            let solution = Solution::new(args.day.unwrap(), args.part.unwrap());
+            if args.options.report {
+                print!("{}", solution.report(&args.options)?);
+            }
+            println!("{}", solution.solve(&args.options)?);
+        }
+        Some(Command::Visualize {
+            day,
+            part,
+            fps,
+            max_frames,
+            gif,
+            scale,
+            cast,
+        }) => {
+            let mut sim = Solution::new(day, part).simulation(max_frames)?;
+            if let Some(path) = gif {
+                let file = BufWriter::new(File::create(path)?);
+                simulation::write_gif(sim.as_mut(), file, fps, scale, max_frames)?;
+            } else if let Some(path) = cast {
+                let file = BufWriter::new(File::create(path)?);
+                simulation::write_cast(sim.as_mut(), file, fps, max_frames)?;
+            } else {
+                simulation::play(sim.as_mut(), fps, max_frames)?;
+            }
+        }
+    }
 
     Ok(())
 }
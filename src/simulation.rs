@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use color_eyre::eyre::{bail, Result};
+use json::JsonValue;
+
+use crate::common::render::{Canvas, Rgb};
+
+/// A puzzle that can be run step by step and looked at in between.
+pub trait Simulation {
+    /// Advances by one step, returns `false` once there is nothing left to do.
+    fn step(&mut self) -> bool;
+
+    /// Current state, each cell an index into [`Simulation::palette`].
+    /// All frames of a simulation have the same size.
+    fn frame(&self) -> Canvas<u8>;
+
+    /// How each cell value is drawn in the terminal and in images.
+    fn palette(&self) -> Vec<(char, Rgb)>;
+}
+
+/// Initial frame followed by one frame per step, at most `max_frames` of them.
+fn frames(
+    sim: &mut dyn Simulation,
+    max_frames: Option<usize>,
+) -> impl Iterator<Item = Canvas<u8>> + '_ {
+    let mut done = false;
+    let initial = sim.frame();
+    let rest = std::iter::from_fn(move || {
+        if done || !sim.step() {
+            done = true;
+            None
+        } else {
+            Some(sim.frame())
+        }
+    });
+    std::iter::once(initial)
+        .chain(rest)
+        .take(max_frames.unwrap_or(usize::MAX))
+}
+
+fn ansi_frame(frame: &Canvas<u8>, palette: &[(char, Rgb)]) -> String {
+    // home the cursor and clear the screen first
+    format!(
+        "\x1b[H\x1b[2J{}",
+        frame.ansi(
+            |&c| palette[usize::from(c)].0,
+            |&c| palette[usize::from(c)].1
+        )
+    )
+}
+
+/// Plays the simulation in the terminal.
+pub fn play(sim: &mut dyn Simulation, fps: u32, max_frames: Option<usize>) -> Result<()> {
+    let palette = sim.palette();
+    let delay = Duration::from_secs(1) / fps;
+    let mut stdout = std::io::stdout().lock();
+    for frame in frames(sim, max_frames) {
+        writeln!(stdout, "{}", ansi_frame(&frame, &palette))?;
+        stdout.flush()?;
+        thread::sleep(delay);
+    }
+    Ok(())
+}
+
+/// Writes an animated GIF, each cell drawn as a `scale` x `scale` square.
+pub fn write_gif(
+    sim: &mut dyn Simulation,
+    w: impl Write,
+    fps: u32,
+    scale: usize,
+    max_frames: Option<usize>,
+) -> Result<()> {
+    let palette: Vec<u8> = sim.palette().iter().flat_map(|(_, rgb)| *rgb).collect();
+    let first = sim.frame();
+    let width = u16::try_from(first.width() * scale)?;
+    let height = u16::try_from(first.height() * scale)?;
+    let mut encoder = gif::Encoder::new(w, width, height, &palette)?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    // GIF delays are in hundredths of a second, and a delay of 0 plays at the viewer's pace
+    let delay = u16::try_from((100 / fps.max(1)).max(1))?;
+    for frame in frames(sim, max_frames) {
+        if (frame.width(), frame.height()) != (first.width(), first.height()) {
+            bail!("frame size changed during the simulation");
+        }
+        let scaled = frame.scaled(scale);
+        let mut gif_frame = gif::Frame::from_indexed_pixels(width, height, scaled.cells(), None);
+        gif_frame.delay = delay;
+        encoder.write_frame(&gif_frame)?;
+    }
+    Ok(())
+}
+
+/// Writes an asciicast v2 recording that can be replayed with `asciinema play`.
+pub fn write_cast(
+    sim: &mut dyn Simulation,
+    mut w: impl Write,
+    fps: u32,
+    max_frames: Option<usize>,
+) -> Result<()> {
+    let palette = sim.palette();
+    let first = sim.frame();
+    let header = json::object! {
+        version: 2,
+        width: first.width(),
+        // one more line for the cursor after the frame
+        height: first.height() + 1,
+    };
+    writeln!(w, "{}", header.dump())?;
+    for (i, frame) in frames(sim, max_frames).enumerate() {
+        let time = f64::from(u32::try_from(i)?) / f64::from(fps);
+        let output = ansi_frame(&frame, &palette).replace('\n', "\r\n") + "\r\n";
+        let event = JsonValue::Array(vec![time.into(), "o".into(), output.into()]);
+        writeln!(w, "{}", event.dump())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frames, write_cast, write_gif, Simulation};
+    use crate::common::render::{Canvas, Rgb};
+
+    /// A dot crossing a 3-cell line.
+    struct Dot(usize);
+
+    impl Simulation for Dot {
+        fn step(&mut self) -> bool {
+            self.0 += 1;
+            self.0 < 3
+        }
+
+        fn frame(&self) -> Canvas<u8> {
+            Canvas::new(3, 1, (0..3).map(|i| u8::from(i == self.0)).collect())
+        }
+
+        fn palette(&self) -> Vec<(char, Rgb)> {
+            vec![('.', [0, 0, 0]), ('o', [255, 255, 255])]
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        let all: Vec<_> = frames(&mut Dot(0), None)
+            .map(|f| f.ascii(|&c| char::from(b'0' + c)))
+            .collect();
+        assert_eq!(vec!["100", "010", "001"], all);
+        assert_eq!(2, frames(&mut Dot(0), Some(2)).count());
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut out = vec![];
+        write_cast(&mut Dot(0), &mut out, 2, None).unwrap();
+        let lines: Vec<_> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(4, lines.len());
+        assert_eq!(r#"{"version":2,"width":3,"height":2}"#, lines[0]);
+        let event = json::parse(&lines[2]).unwrap();
+        assert_eq!("0.5", event[0].dump());
+        assert!(event[2]
+            .as_str()
+            .unwrap()
+            .ends_with(".\x1b[38;2;255;255;255mo\x1b[38;2;0;0;0m.\x1b[0m\r\n"));
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut out = vec![];
+        write_gif(&mut Dot(0), &mut out, 10, 2, None).unwrap();
+        assert_eq!(b"GIF89a", &out[..6]);
+    }
+
+    #[test]
+    fn test_gif_delay() {
+        for (fps, delay) in [(4, 25), (100, 1), (1000, 1)] {
+            let mut out = vec![];
+            write_gif(&mut Dot(0), &mut out, fps, 1, None).unwrap();
+            let mut decoder = gif::DecodeOptions::new().read_info(out.as_slice()).unwrap();
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            assert_eq!(delay, frame.delay, "{fps} fps");
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufRead;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use clap::builder::RangedU64ValueParser;
+use color_eyre::eyre::{bail, Result, WrapErr};
 
 use crate::day1;
 use crate::day10;
@@ -19,6 +23,129 @@ use crate::day6;
 use crate::day7;
 use crate::day8;
 use crate::day9;
+use crate::simulation::Simulation;
+
+/// Knobs that only some of the puzzles look at.
+#[derive(clap::Args, Debug, Default)]
+pub struct Options {
+    /// Print a detailed report before the answer
+    #[arg(long)]
+    pub report: bool,
+    /// Day 1: add up the calories of this many elves
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub top: Option<usize>,
+    /// Day 2: play by the rules in this JSON file instead of the puzzle's
+    #[arg(long)]
+    pub rules: Option<PathBuf>,
+    /// Day 3: look for badges in groups of this many elves
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub group_size: Option<usize>,
+    /// Day 4: also list the sections covered by more than this many elves
+    #[arg(long)]
+    pub more_than: Option<usize>,
+    /// Day 5: operate this crane model and count its lifts
+    #[arg(long, value_enum)]
+    pub crane: Option<CraneModel>,
+    /// Day 5: crates the limited crane can lift at once
+    #[arg(
+        long,
+        required_if_eq("crane", "limited"),
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub capacity: Option<usize>,
+    /// Day 5: draw the stacks after this many instructions instead
+    #[arg(long)]
+    pub after_step: Option<usize>,
+    /// Day 5: draw the stacks after running everything and undoing this many instructions
+    #[arg(long, conflicts_with = "after_step")]
+    pub undo: Option<usize>,
+    /// Day 5: plan the fewest instructions turning the input's stacks into this diagram,
+    /// giving up after 500000 stack layouts
+    #[arg(long)]
+    pub target: Option<PathBuf>,
+    /// Day 6: look for this many distinct characters, streaming the input
+    #[arg(long)]
+    pub window: Option<usize>,
+    /// Day 7: draw the filesystem as a tree or list its directories by size
+    #[arg(long, value_enum, requires = "report")]
+    pub listing: Option<ListingStyle>,
+    /// Day 7: only report directories of at least this many bytes
+    #[arg(long, requires = "report")]
+    pub min_size: Option<usize>,
+    /// Day 7: only report directories of at most this many bytes
+    #[arg(long, requires = "report")]
+    pub max_size: Option<usize>,
+    /// Day 7: use a transcript of this local directory as the input
+    #[arg(long)]
+    pub scan: Option<PathBuf>,
+    /// Day 7: also create the input's filesystem under this directory, with sparse files
+    #[arg(long)]
+    pub materialize: Option<PathBuf>,
+    /// Day 7: answer with the size of the directory at this path, such as /a/e
+    #[arg(long)]
+    pub path: Option<String>,
+    /// Day 7: size of the disk in bytes
+    #[arg(long)]
+    pub disk_size: Option<usize>,
+    /// Day 7: free space the update needs in bytes
+    #[arg(long)]
+    pub needed: Option<usize>,
+    /// Day 7: delete any directories, keeping this as low as possible. Past 4194304
+    /// bytes, sizes are compared in coarser units and the excess may be slightly above
+    /// the least possible
+    #[arg(long, value_enum)]
+    pub minimize: Option<DeletionGoal>,
+    /// Day 8: list the trees seen in each direction from this position, given as x,y
+    #[arg(long, value_parser = parse_position, allow_hyphen_values = true)]
+    pub from: Option<(i64, i64)>,
+    /// Day 8: colour the forest by visibility or scenic score
+    #[arg(long, value_enum)]
+    pub heatmap: Option<HeatmapStyle>,
+    /// Day 8: also write the heatmap to this PNG file
+    #[arg(long)]
+    pub image: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum CraneModel {
+    #[value(name = "9000")]
+    CrateMover9000,
+    #[value(name = "9001")]
+    CrateMover9001,
+    Limited,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum ListingStyle {
+    Tree,
+    Du,
+    Transcript,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum DeletionGoal {
+    Excess,
+    Deletions,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum HeatmapStyle {
+    Visibility,
+    Scenic,
+}
+
+fn parse_position(s: &str) -> Result<(i64, i64), String> {
+    let (x, y) = s.split_once(',').ok_or("expected x,y")?;
+    let coordinate = |v: &str| v.trim().parse::<i64>().map_err(|e| format!("{v}: {e}"));
+    Ok((coordinate(x)?, coordinate(y)?))
+}
+
+fn load_rules(path: &Path) -> Result<day2::Rules> {
+    fs::read_to_string(path)
+        .map_err(Into::into)
+        .and_then(|s| day2::Rules::parse(&s))
+        .wrap_err_with(|| format!("rules file {}", path.display()))
+}
 
 pub struct Solution {
     day: u8,
@@ -39,39 +166,109 @@ impl Solution {
         BufReader::new(file).lines().map(Result::unwrap).collect()
     }
 
-    pub fn solve(&self) -> String {
-        let input = self.read_input();
+    /// The puzzle input, unless the options provide another one.
+    fn input(&self, options: &Options) -> Result<Vec<String>> {
+        if let (7, Some(dir)) = (self.day, &options.scan) {
+            return Ok(day7::FileSystem::scan(dir)?.transcript());
+        }
+        Ok(self.read_input())
+    }
+
+    pub fn solve(&self, options: &Options) -> Result<String> {
+        if let Self { day: 6, part } = self {
+            let file = File::open(self.input_filename())?;
+            let marker = match (options.window, part) {
+                (Some(n), _) => day6::text_marker(file, n)?,
+                (None, 1) => day6::solution1(file)?,
+                (None, 2) => day6::solution2(file)?,
+                (None, _) => unimplemented!("this solution does not exist yet"),
+            };
+            return Ok(marker.to_string());
+        }
+
+        let input = self.input(options)?;
+
+        if let (1, Some(k)) = (self.day, options.top) {
+            return Ok(day1::top_total(&input, k)?.to_string());
+        }
+        if let (2, Some(path)) = (self.day, &options.rules) {
+            return Ok(load_rules(path)?.play(&input)?.to_string());
+        }
+        if let (3, Some(k)) = (self.day, options.group_size) {
+            return Ok(day3::badge_total(&input, k)?.to_string());
+        }
+        if self.day == 5 {
+            let crane = self.crane(options);
+            if let Some(path) = &options.target {
+                let file = File::open(path)
+                    .wrap_err_with(|| format!("target diagram {}", path.display()))?;
+                let target: Vec<_> = BufReader::new(file).lines().collect::<Result<_, _>>()?;
+                return day5::plan_moves(&input, &target, crane.as_ref());
+            }
+            if let Some(step) = options.after_step {
+                return day5::render_after(&input, crane.as_ref(), step);
+            }
+            if let Some(steps) = options.undo {
+                return day5::render_undone(&input, crane.as_ref(), steps);
+            }
+            if options.crane.is_some() {
+                let (message, lifts) = day5::operate(&input, crane.as_ref())?;
+                return Ok(format!("{message} ({lifts} lifts)"));
+            }
+        }
+        if let (7, Some(dir)) = (self.day, &options.materialize) {
+            day7::parse(&input)?.materialize(dir)?;
+        }
+        if let (7, Some(path)) = (self.day, &options.path) {
+            return Ok(day7::dir_size(&input, path)?.to_string());
+        }
+        if self.day == 7 && self.part == 2 {
+            let disk = options.disk_size.unwrap_or(70_000_000);
+            let needed = options.needed.unwrap_or(30_000_000);
+            if let Some(goal) = options.minimize {
+                let goal = match goal {
+                    DeletionGoal::Excess => day7::Goal::Excess,
+                    DeletionGoal::Deletions => day7::Goal::Deletions,
+                };
+                return day7::plan_deletions(&input, disk, needed, goal);
+            }
+            return Ok(day7::smallest_deletion(&input, disk, needed)?.to_string());
+        }
+        if let (8, Some(path)) = (self.day, &options.image) {
+            let file = BufWriter::new(
+                File::create(path).wrap_err_with(|| format!("heatmap {}", path.display()))?,
+            );
+            day8::write_heatmap(&input, Self::heatmap(options), file)?;
+        }
 
         // let's not get too clever here
-        match self {
-            Self { day: 1, part: 1 } => day1::solution1(&input).to_string(),
-            Self { day: 1, part: 2 } => day1::solution2(&input).to_string(),
-            Self { day: 2, part: 1 } => day2::solution1(&input).to_string(),
-            Self { day: 2, part: 2 } => day2::solution2(&input).to_string(),
-            Self { day: 3, part: 1 } => day3::solution1(&input).to_string(),
-            Self { day: 3, part: 2 } => day3::solution2(&input).to_string(),
+        Ok(match self {
+            Self { day: 1, part: 1 } => day1::solution1(&input)?.to_string(),
+            Self { day: 1, part: 2 } => day1::solution2(&input)?.to_string(),
+            Self { day: 2, part: 1 } => day2::solution1(&input)?.to_string(),
+            Self { day: 2, part: 2 } => day2::solution2(&input)?.to_string(),
+            Self { day: 3, part: 1 } => day3::solution1(&input)?.to_string(),
+            Self { day: 3, part: 2 } => day3::solution2(&input)?.to_string(),
             Self { day: 4, part: 1 } => day4::solution1(&input).to_string(),
             Self { day: 4, part: 2 } => day4::solution2(&input).to_string(),
-            Self { day: 5, part: 1 } => day5::solution1(&input),
-            Self { day: 5, part: 2 } => day5::solution2(&input),
-            Self { day: 6, part: 1 } => day6::solution1(&input).to_string(),
-            Self { day: 6, part: 2 } => day6::solution2(&input).to_string(),
-            Self { day: 7, part: 1 } => day7::solution1(&input).to_string(),
-            Self { day: 7, part: 2 } => day7::solution2(&input).to_string(),
-            Self { day: 8, part: 1 } => day8::solution1(&input).to_string(),
-            Self { day: 8, part: 2 } => day8::solution2(&input).to_string(),
+            Self { day: 5, part: 1 } => day5::solution1(&input)?,
+            Self { day: 5, part: 2 } => day5::solution2(&input)?,
+            Self { day: 7, part: 1 } => day7::solution1(&input)?.to_string(),
+            Self { day: 7, part: 2 } => day7::solution2(&input)?.to_string(),
+            Self { day: 8, part: 1 } => day8::solution1(&input)?.to_string(),
+            Self { day: 8, part: 2 } => day8::solution2(&input)?.to_string(),
             Self { day: 9, part: 1 } => day9::solution1(&input).to_string(),
             Self { day: 9, part: 2 } => day9::solution2(&input).to_string(),
             Self { day: 10, part: 1 } => day10::solution1(&input).to_string(),
             Self { day: 10, part: 2 } => day10::solution2(&input),
-            Self { day: 11, part: 1 } => day11::solution1(&input).to_string(),
-            Self { day: 11, part: 2 } => day11::solution2(&input).to_string(),
+            Self { day: 11, part: 1 } => day11::solution1(&input)?.to_string(),
+            Self { day: 11, part: 2 } => day11::solution2(&input)?.to_string(),
             Self { day: 12, part: 1 } => day12::solution1(&input).to_string(),
             Self { day: 12, part: 2 } => day12::solution2(&input).to_string(),
-            Self { day: 13, part: 1 } => day13::solution1(&input).to_string(),
-            Self { day: 13, part: 2 } => day13::solution2(&input).to_string(),
-            Self { day: 14, part: 1 } => day14::solution1(&input).to_string(),
-            Self { day: 14, part: 2 } => day14::solution2(&input).to_string(),
+            Self { day: 13, part: 1 } => day13::solution1(&input)?.to_string(),
+            Self { day: 13, part: 2 } => day13::solution2(&input)?.to_string(),
+            Self { day: 14, part: 1 } => day14::solution1(&input)?.to_string(),
+            Self { day: 14, part: 2 } => day14::solution2(&input)?.to_string(),
             Self { day: 15, part: 1 } => day15::solution1(&input).to_string(),
             Self { day: 15, part: 2 } => day15::solution2(&input).to_string(),
             Self { day: 16, part: 1 } => day16::solution1(&input).to_string(),
@@ -79,6 +276,72 @@ impl Solution {
             Self { day: 17, part: 1 } => day17::solution1(&input).to_string(),
             Self { day: 17, part: 2 } => day17::solution2(&input).to_string(),
             _ => unimplemented!("this solution does not exist yet"),
+        })
+    }
+
+    /// The crane picked on the command line, or the one the part is about.
+    fn crane(&self, options: &Options) -> Box<dyn day5::Crane> {
+        let model = options.crane.unwrap_or(if self.part == 1 {
+            CraneModel::CrateMover9000
+        } else {
+            CraneModel::CrateMover9001
+        });
+        match model {
+            CraneModel::CrateMover9000 => Box::new(day5::CrateMover9000),
+            CraneModel::CrateMover9001 => Box::new(day5::CrateMover9001),
+            CraneModel::Limited => Box::new(day5::LimitedCrane::new(options.capacity.unwrap())),
         }
     }
+
+    /// The heatmap picked on the command line, scenic scores by default.
+    fn heatmap(options: &Options) -> day8::Heatmap {
+        match options.heatmap.unwrap_or(HeatmapStyle::Scenic) {
+            HeatmapStyle::Visibility => day8::Heatmap::Visibility,
+            HeatmapStyle::Scenic => day8::Heatmap::Scenic,
+        }
+    }
+
+    pub fn report(&self, options: &Options) -> Result<String> {
+        let input = self.input(options)?;
+
+        Ok(match (self.day, &options.rules) {
+            (1, _) => day1::report(&input)?,
+            (2, None) => day2::report(&input)?,
+            (2, Some(path)) => load_rules(path)?.mapping_report(&input)?,
+            (3, _) => day3::report(&input, options.group_size.unwrap_or(3))?,
+            (4, _) => day4::report(&input, options.more_than)?,
+            (5, _) => day5::report(&input, options.capacity)?,
+            (6, _) => day6::report(&input, options.window.unwrap_or(14))?,
+            (7, _) => {
+                let sizes = options.min_size.unwrap_or(0)..=options.max_size.unwrap_or(usize::MAX);
+                match options.listing.unwrap_or(ListingStyle::Tree) {
+                    ListingStyle::Tree => day7::tree(&input, &sizes)?,
+                    ListingStyle::Du => day7::du(&input, &sizes)?,
+                    ListingStyle::Transcript => day7::transcript(&input)?,
+                }
+            }
+            (8, _) => day8::report(&input, options.from, Self::heatmap(options))?,
+            (day, _) => bail!("day {day} has no report"),
+        })
+    }
+
+    /// The puzzle as a simulation. Those that would run practically forever need
+    /// `max_frames`.
+    pub fn simulation(&self, max_frames: Option<usize>) -> Result<Box<dyn Simulation>> {
+        let input = self.read_input();
+
+        Ok(match self {
+            Self { day: 9, part: 1 } => Box::new(day9::simulation1(&input)),
+            Self { day: 9, part: 2 } => Box::new(day9::simulation2(&input)),
+            Self { day: 10, part: 2 } => Box::new(day10::simulation2(&input)),
+            Self { day: 14, part: 1 } => Box::new(day14::simulation1(&input)?),
+            Self { day: 14, part: 2 } => Box::new(day14::simulation2(&input)?),
+            Self { day: 17, part: 1 } => Box::new(day17::simulation1(&input)),
+            Self { day: 17, part: 2 } if max_frames.is_none() => {
+                bail!("day 17 part 2 drops a trillion rocks, limit it with --max-frames")
+            }
+            Self { day: 17, part: 2 } => Box::new(day17::simulation2(&input)),
+            Self { day, part } => bail!("day {day} part {part} has nothing to visualize"),
+        })
+    }
 }